@@ -0,0 +1,311 @@
+//! Synchronous equivalents of the async functions, for scripts and CLI tools that don't
+//! want to bring their own async runtime. The futures are driven by a runtime that's
+//! managed internally, much like reqwest's own `blocking` module.
+//!
+//! These functions must not be called from within an async runtime, as blocking
+//! inside of one panics.
+
+#[cfg(feature = "payments")]
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::api_context::{self, ApiContext, ContextStore, Environment, ManagedApiContext};
+#[cfg(feature = "payments")]
+use crate::common::ListParams;
+use crate::common::{SetupContext, SetupProfiles};
+use crate::domains::device::{self, Device};
+#[cfg(feature = "monetary-accounts")]
+use crate::domains::monetary_account::{
+    self, Amount, MonetaryAccount, MonetaryAccountId, OwnIban, SavingsGoal,
+};
+use crate::domains::oauth;
+#[cfg(feature = "payments")]
+use crate::domains::payment::{self, Mutation, Payment, PaymentId};
+use crate::domains::user::{self, WhoAmI};
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// Drive the future to completion on the internal runtime, creating it on first use
+fn block_on<F: Future>(future: F) -> anyhow::Result<F::Output> {
+    let runtime = match RUNTIME.get() {
+        Some(runtime) => runtime,
+        None => {
+            let runtime = Builder::new_multi_thread().enable_all().build()?;
+            RUNTIME.get_or_init(|| runtime)
+        }
+    };
+
+    Ok(runtime.block_on(future))
+}
+
+/// See [`user::whoami`]
+pub fn whoami(api_context: &ManagedApiContext) -> anyhow::Result<WhoAmI> {
+    block_on(user::whoami(api_context))?
+}
+
+/// See [`user::ping`]
+pub fn ping(api_context: &ManagedApiContext) -> anyhow::Result<Duration> {
+    block_on(user::ping(api_context))?
+}
+
+/// See [`device::get_current_device`]
+pub fn get_current_device(api_context: &ManagedApiContext) -> anyhow::Result<Device> {
+    block_on(device::get_current_device(api_context))?
+}
+
+/// See [`api_context::get_api_context`]
+pub fn get_api_context(
+    setup_context: &SetupContext,
+    store: &dyn ContextStore,
+) -> anyhow::Result<ApiContext> {
+    block_on(api_context::get_api_context(setup_context, store))?
+}
+
+/// See [`api_context::get_api_context_at_storage_path`]
+pub fn get_api_context_at_storage_path(setup_context: &SetupContext) -> anyhow::Result<ApiContext> {
+    block_on(api_context::get_api_context_at_storage_path(setup_context))?
+}
+
+/// See [`api_context::get_api_context_for_environment`]
+pub fn get_api_context_for_environment(
+    profiles: &SetupProfiles,
+    environment: Environment,
+) -> anyhow::Result<ApiContext> {
+    block_on(api_context::get_api_context_for_environment(
+        profiles,
+        environment,
+    ))?
+}
+
+/// See [`api_context::logout`]
+pub fn logout(store: &dyn ContextStore) -> anyhow::Result<()> {
+    block_on(api_context::logout(store))?
+}
+
+/// See [`api_context::logout_at_storage_path`]
+pub fn logout_at_storage_path(setup_context: &SetupContext) -> anyhow::Result<()> {
+    block_on(api_context::logout_at_storage_path(setup_context))?
+}
+
+/// See [`api_context::rotate_installation`]
+pub fn rotate_installation(
+    api_context: &ManagedApiContext,
+    setup_context: &SetupContext,
+    store: &dyn ContextStore,
+) -> anyhow::Result<ApiContext> {
+    block_on(api_context::rotate_installation(
+        api_context,
+        setup_context,
+        store,
+    ))?
+}
+
+/// See [`api_context::rotate_installation_at_storage_path`]
+pub fn rotate_installation_at_storage_path(
+    api_context: &ManagedApiContext,
+    setup_context: &SetupContext,
+) -> anyhow::Result<ApiContext> {
+    block_on(api_context::rotate_installation_at_storage_path(
+        api_context,
+        setup_context,
+    ))?
+}
+
+/// See [`oauth::complete_oauth`]
+pub fn complete_oauth(setup_context: &SetupContext, code: &str) -> anyhow::Result<ApiContext> {
+    block_on(oauth::complete_oauth(setup_context, code))?
+}
+
+/// See [`oauth::complete_oauth_with_pkce`]
+pub fn complete_oauth_with_pkce(
+    setup_context: &SetupContext,
+    code: &str,
+    code_verifier: &oauth::PkceVerifier,
+) -> anyhow::Result<ApiContext> {
+    block_on(oauth::complete_oauth_with_pkce(
+        setup_context,
+        code,
+        code_verifier,
+    ))?
+}
+
+/// See [`monetary_account::get_monetary_accounts`]
+#[cfg(feature = "monetary-accounts")]
+pub fn get_monetary_accounts(
+    api_context: &ManagedApiContext,
+) -> anyhow::Result<Vec<MonetaryAccount>> {
+    block_on(monetary_account::get_monetary_accounts(api_context))?
+}
+
+/// See [`monetary_account::get_monetary_account`]
+#[cfg(feature = "monetary-accounts")]
+pub fn get_monetary_account(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<MonetaryAccount> {
+    block_on(monetary_account::get_monetary_account(
+        api_context,
+        monetary_account_id,
+    ))?
+}
+
+/// See [`monetary_account::get_balance`]
+#[cfg(feature = "monetary-accounts")]
+pub fn get_balance(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<Amount> {
+    block_on(monetary_account::get_balance(
+        api_context,
+        monetary_account_id,
+    ))?
+}
+
+/// See [`monetary_account::get_savings_goal`]
+#[cfg(feature = "monetary-accounts")]
+pub fn get_savings_goal(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<Option<SavingsGoal>> {
+    block_on(monetary_account::get_savings_goal(
+        api_context,
+        monetary_account_id,
+    ))?
+}
+
+/// See [`monetary_account::set_savings_goal`]
+#[cfg(feature = "monetary-accounts")]
+pub fn set_savings_goal(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    target: Amount,
+) -> anyhow::Result<SavingsGoal> {
+    block_on(monetary_account::set_savings_goal(
+        api_context,
+        monetary_account_id,
+        target,
+    ))?
+}
+
+/// See [`monetary_account::get_active_monetary_accounts`]
+#[cfg(feature = "monetary-accounts")]
+pub fn get_active_monetary_accounts(
+    api_context: &ManagedApiContext,
+) -> anyhow::Result<Vec<MonetaryAccount>> {
+    block_on(monetary_account::get_active_monetary_accounts(api_context))?
+}
+
+/// See [`monetary_account::get_own_ibans`]
+#[cfg(feature = "monetary-accounts")]
+pub fn get_own_ibans(api_context: &ManagedApiContext) -> anyhow::Result<Vec<OwnIban>> {
+    block_on(monetary_account::get_own_ibans(api_context))?
+}
+
+/// See [`monetary_account::update_monetary_account`]
+#[cfg(feature = "monetary-accounts")]
+pub fn update_monetary_account(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    description: Option<&str>,
+    daily_limit: Option<Amount>,
+) -> anyhow::Result<MonetaryAccount> {
+    block_on(monetary_account::update_monetary_account(
+        api_context,
+        monetary_account_id,
+        description,
+        daily_limit,
+    ))?
+}
+
+/// See [`payment::get_payments`]
+#[cfg(feature = "payments")]
+pub fn get_payments(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    list_params: Option<ListParams>,
+) -> anyhow::Result<Vec<Payment>> {
+    block_on(payment::get_payments(
+        api_context,
+        monetary_account_id,
+        list_params,
+    ))?
+}
+
+/// See [`payment::get_payments_with_count`]
+#[cfg(feature = "payments")]
+pub fn get_payments_with_count(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    count: u32,
+) -> anyhow::Result<Vec<Payment>> {
+    block_on(payment::get_payments_with_count(
+        api_context,
+        monetary_account_id,
+        count,
+    ))?
+}
+
+/// See [`payment::get_payments_for_all_accounts`]
+#[cfg(feature = "payments")]
+pub fn get_payments_for_all_accounts(
+    api_context: &ManagedApiContext,
+    count_per_account: u32,
+) -> anyhow::Result<HashMap<MonetaryAccountId, Vec<Payment>>> {
+    block_on(payment::get_payments_for_all_accounts(
+        api_context,
+        count_per_account,
+    ))?
+}
+
+/// See [`payment::get_payments_chronological`]
+#[cfg(feature = "payments")]
+pub fn get_payments_chronological(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<Vec<Payment>> {
+    block_on(payment::get_payments_chronological(
+        api_context,
+        monetary_account_id,
+    ))?
+}
+
+/// See [`payment::get_all_payments`]
+#[cfg(feature = "payments")]
+pub fn get_all_payments(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<Vec<Payment>> {
+    block_on(payment::get_all_payments(api_context, monetary_account_id))?
+}
+
+/// See [`payment::get_payments_since`]
+#[cfg(feature = "payments")]
+pub fn get_payments_since(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    newer_than_id: PaymentId,
+) -> anyhow::Result<Vec<Payment>> {
+    block_on(payment::get_payments_since(
+        api_context,
+        monetary_account_id,
+        newer_than_id,
+    ))?
+}
+
+/// See [`payment::get_mutations`]
+#[cfg(feature = "payments")]
+pub fn get_mutations(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    list_params: Option<ListParams>,
+) -> anyhow::Result<Vec<Mutation>> {
+    block_on(payment::get_mutations(
+        api_context,
+        monetary_account_id,
+        list_params,
+    ))?
+}
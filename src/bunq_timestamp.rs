@@ -0,0 +1,117 @@
+//! bunq formats its timestamps without a timezone, e.g. `2024-10-14 21:19:14.426211`, and
+//! they are in UTC. Fields holding one can be typed as `DateTime<Utc>` with
+//! `#[serde(with = "crate::bunq_timestamp")]`, or `crate::bunq_timestamp::option` when
+//! they're optional.
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+const BUNQ_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+/// bunq always writes microseconds
+const BUNQ_TIMESTAMP_OUTPUT_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.6f";
+
+/// Parse a timestamp as bunq formats it
+pub fn parse(timestamp: &str) -> anyhow::Result<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(timestamp, BUNQ_TIMESTAMP_FORMAT)
+        .with_context(|| format!("Invalid bunq timestamp: {}", timestamp))?;
+
+    Ok(naive.and_utc())
+}
+
+/// Format a moment the way bunq formats its timestamps
+pub fn format(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format(BUNQ_TIMESTAMP_OUTPUT_FORMAT).to_string()
+}
+
+pub fn serialize<S>(timestamp: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format(timestamp))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let timestamp = String::deserialize(deserializer)?;
+
+    parse(&timestamp).map_err(|error| D::Error::custom(format!("{:#}", error)))
+}
+
+/// The same as the parent module, for optional timestamps
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S>(timestamp: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match timestamp {
+            Some(timestamp) => super::serialize(timestamp, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|timestamp| {
+                parse(&timestamp).map_err(|error| D::Error::custom(format!("{:#}", error)))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, Timelike};
+    use serde::Serialize;
+
+    use super::*;
+
+    #[test]
+    fn bunq_timestamp_is_parsed_as_utc() {
+        let timestamp = parse("2024-10-14 21:19:14.426211").unwrap();
+
+        assert_eq!(timestamp.year(), 2024);
+        assert_eq!(timestamp.month(), 10);
+        assert_eq!(timestamp.day(), 14);
+        assert_eq!(timestamp.hour(), 21);
+        assert_eq!(timestamp.nanosecond(), 426_211_000);
+    }
+
+    #[test]
+    fn invalid_bunq_timestamp_is_an_error() {
+        assert!(parse("14-10-2024").is_err());
+    }
+
+    #[test]
+    fn timestamp_is_formatted_like_bunq_does() {
+        let timestamp = "2024-10-14 21:19:14.426211";
+
+        assert_eq!(format(&parse(timestamp).unwrap()), timestamp);
+    }
+
+    #[test]
+    fn timestamps_round_trip_through_serde() {
+        #[derive(Serialize, Deserialize, Debug)]
+        struct Times {
+            #[serde(with = "crate::bunq_timestamp")]
+            start: DateTime<Utc>,
+            #[serde(with = "crate::bunq_timestamp::option")]
+            end: Option<DateTime<Utc>>,
+        }
+
+        let json = r#"{"start":"2024-10-14 21:19:14.426211","end":null}"#;
+        let times: Times = serde_json::from_str(json).unwrap();
+
+        assert_eq!(times.start.nanosecond(), 426_211_000);
+        assert!(times.end.is_none());
+        assert_eq!(serde_json::to_string(&times).unwrap(), json);
+    }
+}
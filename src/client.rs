@@ -0,0 +1,289 @@
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "monetary-accounts")]
+use std::time::Instant;
+
+use tokio::sync::Mutex;
+
+use crate::api_context::{ApiContext, ManagedApiContext, RateLimitStatus, UserInformation};
+#[cfg(feature = "card-payments")]
+use crate::domains::card_payment::{get_card_payments, CardPayment};
+#[cfg(feature = "monetary-accounts")]
+use crate::domains::monetary_account::{
+    get_balance, get_monetary_accounts, only_active, own_ibans, set_savings_goal,
+    update_monetary_account, Amount, MonetaryAccount, MonetaryAccountId, OwnIban, SavingsGoal,
+};
+#[cfg(feature = "payments")]
+use crate::domains::payment::{
+    get_mutations, get_payments, get_payments_chronological, Mutation, Payment,
+};
+use crate::domains::user::{ping, whoami, WhoAmI};
+
+/// A client that holds on to the api context, so it doesn't have to be passed around.
+/// It groups the domain functions by the resource they act on:
+///
+/// ```no_run
+/// # use bunqyy::api_context::ManagedApiContext;
+/// # use bunqyy::client::BunqClient;
+/// # #[cfg(all(feature = "monetary-accounts", feature = "payments"))]
+/// # async fn example(api_context: ManagedApiContext) -> anyhow::Result<()> {
+/// let client = BunqClient::new(api_context);
+/// let accounts = client.monetary_accounts().list().await?;
+/// let payments = client.payments(accounts[0].get_id()).list().await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The free functions in the domain modules remain available for more control.
+#[derive(Clone)]
+pub struct BunqClient {
+    api_context: ManagedApiContext,
+    #[cfg(feature = "monetary-accounts")]
+    accounts_cache: AccountsCache,
+}
+
+impl BunqClient {
+    pub fn new(api_context: ManagedApiContext) -> Self {
+        BunqClient {
+            api_context,
+            #[cfg(feature = "monetary-accounts")]
+            accounts_cache: AccountsCache::default(),
+        }
+    }
+
+    /// Reuse the monetary accounts that were listed for the given time, rather than fetching
+    /// them again on every lookup. Caching is off by default, so accounts are never stale.
+    #[cfg(feature = "monetary-accounts")]
+    pub fn with_accounts_cache(self, ttl: Duration) -> Self {
+        BunqClient {
+            accounts_cache: AccountsCache {
+                ttl: Some(ttl),
+                ..self.accounts_cache
+            },
+            ..self
+        }
+    }
+
+    /// Forget the cached monetary accounts, so they're fetched again on the next lookup,
+    /// e.g. after an account was created
+    #[cfg(feature = "monetary-accounts")]
+    pub async fn invalidate_accounts(&self) {
+        *self.accounts_cache.entry.lock().await = None;
+    }
+
+    /// The rate limit headers of the latest response bunq sent, if it sent any
+    pub async fn rate_limit(&self) -> Option<RateLimitStatus> {
+        self.api_context.lock().await.rate_limit.clone()
+    }
+
+    /// How many more requests bunq accepts according to the latest response, if it said so,
+    /// e.g. to show in an application
+    pub async fn remaining_requests(&self) -> Option<u32> {
+        self.api_context.lock().await.remaining_requests()
+    }
+
+    /// The managed api context this client uses, for calling the domain functions directly
+    pub fn api_context(&self) -> &ManagedApiContext {
+        &self.api_context
+    }
+
+    /// The monetary accounts of the user
+    #[cfg(feature = "monetary-accounts")]
+    pub fn monetary_accounts(&self) -> MonetaryAccounts<'_> {
+        MonetaryAccounts { client: self }
+    }
+
+    /// The payments of one of the user's monetary accounts
+    #[cfg(feature = "payments")]
+    pub fn payments(&self, monetary_account_id: MonetaryAccountId) -> Payments<'_> {
+        Payments {
+            client: self,
+            monetary_account_id,
+        }
+    }
+
+    /// The card payments of one of the user's monetary accounts
+    #[cfg(feature = "card-payments")]
+    pub fn card_payments(&self, monetary_account_id: MonetaryAccountId) -> CardPayments<'_> {
+        CardPayments {
+            client: self,
+            monetary_account_id,
+        }
+    }
+
+    /// The user the api context acts on behalf of
+    pub fn user(&self) -> User<'_> {
+        User { client: self }
+    }
+}
+
+impl From<ApiContext> for BunqClient {
+    fn from(api_context: ApiContext) -> Self {
+        BunqClient::new(Arc::new(Mutex::new(api_context)))
+    }
+}
+
+/// The monetary accounts as they were last listed, shared between clones of the client
+#[cfg(feature = "monetary-accounts")]
+#[derive(Clone, Default)]
+struct AccountsCache {
+    ttl: Option<Duration>,
+    entry: Arc<Mutex<Option<CachedAccounts>>>,
+}
+
+#[cfg(feature = "monetary-accounts")]
+struct CachedAccounts {
+    fetched_at: Instant,
+    accounts: Vec<MonetaryAccount>,
+}
+
+#[cfg(feature = "monetary-accounts")]
+pub struct MonetaryAccounts<'a> {
+    client: &'a BunqClient,
+}
+
+#[cfg(feature = "monetary-accounts")]
+impl MonetaryAccounts<'_> {
+    /// List the accounts, from the cache when it's enabled and still fresh
+    pub async fn list(&self) -> anyhow::Result<Vec<MonetaryAccount>> {
+        let cache = &self.client.accounts_cache;
+
+        let Some(ttl) = cache.ttl else {
+            return get_monetary_accounts(&self.client.api_context).await;
+        };
+
+        let mut entry = cache.entry.lock().await;
+
+        if let Some(cached) = entry.as_ref() {
+            if cached.fetched_at.elapsed() < ttl {
+                return Ok(cached.accounts.clone());
+            }
+        }
+
+        let accounts = get_monetary_accounts(&self.client.api_context).await?;
+        *entry = Some(CachedAccounts {
+            fetched_at: Instant::now(),
+            accounts: accounts.clone(),
+        });
+
+        Ok(accounts)
+    }
+
+    /// The current balance of a single account, always fetched rather than taken from the cache
+    pub async fn balance(&self, monetary_account_id: MonetaryAccountId) -> anyhow::Result<Amount> {
+        get_balance(&self.client.api_context, monetary_account_id).await
+    }
+
+    /// List only the accounts that are active
+    pub async fn list_active(&self) -> anyhow::Result<Vec<MonetaryAccount>> {
+        Ok(only_active(self.list().await?))
+    }
+
+    /// The IBANs of the user's own accounts
+    pub async fn own_ibans(&self) -> anyhow::Result<Vec<OwnIban>> {
+        Ok(own_ibans(&self.list().await?))
+    }
+
+    /// Update the description and/or daily limit of a bank account
+    pub async fn update(
+        &self,
+        monetary_account_id: MonetaryAccountId,
+        description: Option<&str>,
+        daily_limit: Option<Amount>,
+    ) -> anyhow::Result<MonetaryAccount> {
+        let account = update_monetary_account(
+            &self.client.api_context,
+            monetary_account_id,
+            description,
+            daily_limit,
+        )
+        .await?;
+
+        self.client.invalidate_accounts().await;
+
+        Ok(account)
+    }
+
+    /// Set the amount to save up to on a savings account
+    pub async fn set_savings_goal(
+        &self,
+        monetary_account_id: MonetaryAccountId,
+        target: Amount,
+    ) -> anyhow::Result<SavingsGoal> {
+        let goal = set_savings_goal(&self.client.api_context, monetary_account_id, target).await?;
+
+        self.client.invalidate_accounts().await;
+
+        Ok(goal)
+    }
+}
+
+#[cfg(feature = "payments")]
+pub struct Payments<'a> {
+    client: &'a BunqClient,
+    monetary_account_id: MonetaryAccountId,
+}
+
+#[cfg(feature = "payments")]
+impl Payments<'_> {
+    /// List the most recent payments, newest first
+    pub async fn list(&self) -> anyhow::Result<Vec<Payment>> {
+        get_payments(&self.client.api_context, self.monetary_account_id, None).await
+    }
+
+    /// List the most recent payments, oldest first
+    pub async fn list_chronological(&self) -> anyhow::Result<Vec<Payment>> {
+        get_payments_chronological(&self.client.api_context, self.monetary_account_id).await
+    }
+
+    /// List the payments as compact mutations, e.g. for a balance graph
+    pub async fn mutations(&self) -> anyhow::Result<Vec<Mutation>> {
+        get_mutations(&self.client.api_context, self.monetary_account_id, None).await
+    }
+}
+
+#[cfg(feature = "card-payments")]
+pub struct CardPayments<'a> {
+    client: &'a BunqClient,
+    monetary_account_id: MonetaryAccountId,
+}
+
+#[cfg(feature = "card-payments")]
+impl CardPayments<'_> {
+    pub async fn list(&self) -> anyhow::Result<Vec<CardPayment>> {
+        get_card_payments(&self.client.api_context, self.monetary_account_id, None).await
+    }
+}
+
+pub struct User<'a> {
+    client: &'a BunqClient,
+}
+
+impl User<'_> {
+    /// The id of the user, which most endpoints are scoped by
+    pub async fn id(&self) -> u64 {
+        self.client.api_context.lock().await.session_context.user_id
+    }
+
+    /// Fetch the user from bunq, confirming the api context still works
+    pub async fn whoami(&self) -> anyhow::Result<WhoAmI> {
+        whoami(&self.client.api_context).await
+    }
+
+    /// Make a lightweight request to keep the session alive, returning its round-trip duration
+    pub async fn ping(&self) -> anyhow::Result<Duration> {
+        ping(&self.client.api_context).await
+    }
+
+    /// The user that granted access to the application, as known from the current session
+    pub async fn get(&self) -> UserInformation {
+        self.client
+            .api_context
+            .lock()
+            .await
+            .session_context
+            .user_api_key
+            .granted_by_user
+            .clone()
+    }
+}
@@ -1,9 +1,14 @@
 use std::fmt::Display;
 
-use crate::api_context::Environment;
+use crate::api_context::{Environment, FileContextStore};
+pub use crate::http::BunqError;
+use crate::signing::random_bytes;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use url::Url;
 
-pub(crate) const BUNQ_BASE_URL: &str = "https://api.bunq.com/v1";
+pub(crate) const BUNQ_PRODUCTION_BASE_URL: &str = "https://api.bunq.com/v1";
+pub(crate) const BUNQ_SANDBOX_BASE_URL: &str = "https://public-api.sandbox.bunq.com/v1";
 
 #[derive(Debug, Error)]
 pub enum BunqyyError {
@@ -12,6 +17,44 @@ pub enum BunqyyError {
     ResponseDeserialization(String),
     MissingDataToBuildApiContext,
     CsvError(String),
+    UnsupportedAttachmentType(String, String),
+    InvalidCount(u32),
+    InvalidAuthorizationCode(String),
+    /// bunq (or something in between) refused the credentials, with the status code
+    Unauthorized(u16),
+    RateLimited,
+    /// bunq failed to handle the request, with the status code
+    ServerError(u16),
+    /// A status code that isn't expected and came without a response bunq can be understood from
+    UnexpectedStatus(u16),
+    /// The OAuth token endpoint refused to hand out an access token, e.g. `invalid_grant`
+    /// when the authorization code expired
+    OAuth {
+        error: String,
+        description: Option<String>,
+    },
+    /// The api context stored at the path can't be read, with the reason
+    CorruptApiContext(String, String),
+    /// Amounts in different currencies can't be added up, with both currencies
+    CurrencyMismatch(String, String),
+    /// The description of a payment is longer than bunq accepts, with its length
+    DescriptionTooLong(usize),
+    /// A response wasn't signed by bunq, or its signature didn't match
+    ResponseSignatureInvalid,
+    /// No credentials were configured for the environment
+    MissingProfile(Environment),
+    /// The stored api context was set up for another environment than the one expected
+    EnvironmentMismatch {
+        stored: Environment,
+        expected: Environment,
+    },
+    /// The oauth redirect came back without the state the flow was started with, so it may
+    /// not be the result of this flow
+    OAuthStateMismatch,
+    /// A private key that was passed in can't be used for an installation, with the reason
+    InvalidPrivateKey(String),
+    /// A notification category that isn't modelled can't be set, as its name isn't known
+    UnknownNotificationCategory,
 }
 
 impl Display for BunqyyError {
@@ -26,6 +69,72 @@ impl Display for BunqyyError {
                 write!(f, "Missing data to build api context")
             }
             BunqyyError::CsvError(e) => write!(f, "CSV error: {}", e),
+            BunqyyError::UnsupportedAttachmentType(extension, supported) => write!(
+                f,
+                "Unsupported attachment type \"{}\", supported are: {}",
+                extension, supported
+            ),
+            BunqyyError::InvalidCount(count) => write!(
+                f,
+                "Invalid count {}, bunq accepts a count of 1 up to and including 200",
+                count
+            ),
+            BunqyyError::InvalidAuthorizationCode(code) => write!(
+                f,
+                "Invalid authorization code \"{}\", copy the code from the redirect url",
+                code
+            ),
+            BunqyyError::Unauthorized(status) => write!(
+                f,
+                "Not authorized ({}), the api context may need to be set up again",
+                status
+            ),
+            BunqyyError::RateLimited => write!(f, "Rate limited by bunq, slow down and retry"),
+            BunqyyError::ServerError(status) => write!(f, "bunq failed with status {}", status),
+            BunqyyError::UnexpectedStatus(status) => {
+                write!(f, "Unexpected response with status {}", status)
+            }
+            BunqyyError::OAuth { error, description } => match description {
+                Some(description) => write!(f, "OAuth error {}: {}", error, description),
+                None => write!(f, "OAuth error {}", error),
+            },
+            BunqyyError::CorruptApiContext(path, reason) => write!(
+                f,
+                "Invalid api context at {}, remove it to set up a new one: {}",
+                path, reason
+            ),
+            BunqyyError::CurrencyMismatch(left, right) => {
+                write!(f, "Cannot add up amounts in {} and {}", left, right)
+            }
+            BunqyyError::DescriptionTooLong(length) => write!(
+                f,
+                "Description of {} characters is too long, bunq accepts up to 140",
+                length
+            ),
+            BunqyyError::ResponseSignatureInvalid => {
+                write!(f, "The response is not signed by bunq")
+            }
+            BunqyyError::MissingProfile(environment) => write!(
+                f,
+                "No credentials configured for {}",
+                <&str>::from(*environment)
+            ),
+            BunqyyError::EnvironmentMismatch { stored, expected } => write!(
+                f,
+                "The stored api context is for {} but {} was expected",
+                <&str>::from(*stored),
+                <&str>::from(*expected)
+            ),
+            BunqyyError::InvalidPrivateKey(reason) => {
+                write!(f, "Invalid private key: {}", reason)
+            }
+            BunqyyError::OAuthStateMismatch => write!(
+                f,
+                "The oauth redirect does not belong to this flow, its state does not match"
+            ),
+            BunqyyError::UnknownNotificationCategory => {
+                write!(f, "Cannot set a notification category that is not known")
+            }
         }
     }
 }
@@ -44,6 +153,89 @@ impl From<reqwest::Error> for BunqyyError {
     }
 }
 
+/// A key that marks requests as being the same, sent as bunq's `X-Bunq-Client-Request-Id`
+/// header. bunq processes a mutating request only once per key, so a request that timed out
+/// can be retried with the same key without e.g. uploading or paying twice.
+/// Retries within the client reuse the request as a whole, and with it this key.
+/// Requests that aren't given a key get a random one, which only covers those retries.
+/// bunq signs bodies only, so the key isn't part of the signature.
+///
+/// Accepted by the functions that create something at bunq:
+/// - [`upload_attachment`](crate::domains::attachment::upload_attachment)
+/// - [`create_payment_with_idempotency_key`](crate::domains::payment::create_payment_with_idempotency_key)
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    /// Generate a new random key
+    pub fn new() -> Self {
+        let bytes = random_bytes::<16>();
+
+        IdempotencyKey(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Default for IdempotencyKey {
+    fn default() -> Self {
+        IdempotencyKey::new()
+    }
+}
+
+impl From<String> for IdempotencyKey {
+    fn from(key: String) -> Self {
+        IdempotencyKey(key)
+    }
+}
+
+impl From<&str> for IdempotencyKey {
+    fn from(key: &str) -> Self {
+        IdempotencyKey(key.to_string())
+    }
+}
+
+/// A way to reach a user or account, such as its IBAN or an email address
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct Alias {
+    pub r#type: AliasType,
+    pub value: String,
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AliasType {
+    Iban,
+    Email,
+    PhoneNumber,
+    /// A type of alias that's not modelled (yet)
+    #[serde(other)]
+    Other,
+}
+
+/// Lookups on the list of aliases of a user or account
+pub trait FindAlias {
+    fn find_alias(&self, alias_type: AliasType) -> Option<&Alias>;
+
+    fn find_iban(&self) -> Option<&Alias> {
+        self.find_alias(AliasType::Iban)
+    }
+
+    fn find_email(&self) -> Option<&Alias> {
+        self.find_alias(AliasType::Email)
+    }
+}
+
+impl FindAlias for [Alias] {
+    fn find_alias(&self, alias_type: AliasType) -> Option<&Alias> {
+        self.iter().find(|alias| alias.r#type == alias_type)
+    }
+}
+
 /// The SetupContext is used in the oauth flow
 #[derive(Clone)]
 pub struct SetupContext {
@@ -51,9 +243,24 @@ pub struct SetupContext {
     pub client_id: String,
     pub client_secret: String,
     pub storage_path: String,
+    /// Make requests to this url instead of bunq's api, e.g. to test against a mock server
+    pub base_url: Option<String>,
+    /// Perform the oauth flow against this url instead of bunq's, the access token is
+    /// requested at `{oauth_base_url}/token` and access is granted at `{oauth_base_url}/auth`
+    pub oauth_base_url: Option<String>,
+    /// How the device shows up in the bunq app, to tell several installations apart
+    pub device_description: String,
+    /// Capture the code of the oauth flow by listening on the redirect uri, rather than
+    /// asking the user to paste it
+    pub capture_redirect: bool,
+    /// Register the installation with this PEM encoded private key rather than a freshly
+    /// generated one, see [`crate::signing::keypair_from_private_key_pem`]
+    pub private_key_pem: Option<String>,
 }
 
 impl SetupContext {
+    /// Create a new setup context. A `~` at the start of the storage path and environment
+    /// variables in it (`$VAR` or `${VAR}`) are expanded.
     pub fn new(
         environment: Environment,
         client_id: String,
@@ -64,9 +271,271 @@ impl SetupContext {
             environment,
             client_id,
             client_secret,
+            storage_path: expand_path(storage_path.as_str()),
+            base_url: None,
+            oauth_base_url: None,
+            device_description: default_device_description(),
+            capture_redirect: false,
+            private_key_pem: None,
+        }
+    }
+
+    /// Describe the device differently than "bunqyy on <hostname>"
+    pub fn with_device_description(self, device_description: impl Into<String>) -> Self {
+        SetupContext {
+            device_description: device_description.into(),
+            ..self
+        }
+    }
+
+    /// Make requests to another url than bunq's api, such as a mock server
+    pub fn with_base_url(self, base_url: impl Into<String>) -> Self {
+        SetupContext {
+            base_url: Some(base_url.into()),
+            ..self
+        }
+    }
+
+    /// Perform the oauth flow against another url than bunq's, such as a mock server
+    pub fn with_oauth_base_url(self, oauth_base_url: impl Into<String>) -> Self {
+        SetupContext {
+            oauth_base_url: Some(oauth_base_url.into()),
+            ..self
+        }
+    }
+
+    /// Listen on the redirect uri during the oauth flow, so the code is picked up as soon as
+    /// the user granted access. When the port can't be bound, the code is asked for instead.
+    pub fn with_redirect_capture(self) -> Self {
+        SetupContext {
+            capture_redirect: true,
+            ..self
+        }
+    }
+
+    /// The store that keeps the api context in a file at the storage path
+    pub fn file_store(&self) -> FileContextStore {
+        FileContextStore::new(self.storage_path.as_str())
+    }
+
+    /// Register the installation with an existing private key, e.g. one that was registered
+    /// before. It must be a PEM encoded rsa key of 2048 bits, which is checked when the api
+    /// context is set up.
+    pub fn with_private_key(self, private_key_pem: impl Into<String>) -> Self {
+        SetupContext {
+            private_key_pem: Some(private_key_pem.into()),
+            ..self
+        }
+    }
+
+    /// The base url of the api requests are made to
+    pub(crate) fn api_base_url(&self) -> &str {
+        self.base_url
+            .as_deref()
+            .unwrap_or(self.environment.base_url())
+    }
+}
+
+/// The credentials of both of bunq's environments in one configuration, e.g. to test in
+/// sandbox and run in production. Every environment gets its own storage file, derived from
+/// the shared storage path by suffixing the environment: `.context.json` is stored as
+/// `.context.sandbox.json` and `.context.production.json`.
+#[derive(Clone)]
+pub struct SetupProfiles {
+    storage_path: String,
+    sandbox: Option<(String, String)>,
+    production: Option<(String, String)>,
+}
+
+impl SetupProfiles {
+    pub fn new(storage_path: String) -> SetupProfiles {
+        SetupProfiles {
             storage_path,
+            sandbox: None,
+            production: None,
         }
     }
+
+    /// Configure the client id and secret for an environment
+    pub fn with_profile(
+        self,
+        environment: Environment,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> Self {
+        let credentials = Some((client_id.into(), client_secret.into()));
+        match environment {
+            Environment::SANDBOX => SetupProfiles {
+                sandbox: credentials,
+                ..self
+            },
+            Environment::PRODUCTION => SetupProfiles {
+                production: credentials,
+                ..self
+            },
+        }
+    }
+
+    /// The setup context of an environment, storing its api context in a file of its own
+    pub fn for_environment(&self, environment: Environment) -> Result<SetupContext, BunqyyError> {
+        let credentials = match environment {
+            Environment::SANDBOX => &self.sandbox,
+            Environment::PRODUCTION => &self.production,
+        };
+        let (client_id, client_secret) = credentials
+            .clone()
+            .ok_or(BunqyyError::MissingProfile(environment))?;
+
+        Ok(SetupContext::new(
+            environment,
+            client_id,
+            client_secret,
+            storage_path_for_environment(&self.storage_path, environment),
+        ))
+    }
+}
+
+/// Insert the environment before the extension of the storage path's file name
+fn storage_path_for_environment(storage_path: &str, environment: Environment) -> String {
+    let suffix = match environment {
+        Environment::SANDBOX => "sandbox",
+        Environment::PRODUCTION => "production",
+    };
+    let path = std::path::Path::new(storage_path);
+
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!(
+                "{}.{}.{}",
+                stem.to_string_lossy(),
+                suffix,
+                extension.to_string_lossy()
+            ))
+            .to_string_lossy()
+            .into_owned(),
+        _ => format!("{}.{}", storage_path, suffix),
+    }
+}
+
+/// Expand a leading `~` to the home directory and `$VAR`/`${VAR}` to the value of the
+/// environment variable. Anything that cannot be resolved is left as it is.
+fn expand_path(path: &str) -> String {
+    let path = match (path.strip_prefix('~'), home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            format!("{}{}", home, rest)
+        }
+        _ => path.to_string(),
+    };
+
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path.as_str();
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after_dollar = &rest[start + 1..];
+
+        let (name, remainder) = match after_dollar.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after_dollar),
+            },
+            None => {
+                let end = after_dollar
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after_dollar.len());
+                (&after_dollar[..end], &after_dollar[end..])
+            }
+        };
+
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => expanded.push_str(&value),
+            _ => expanded.push_str(&rest[start..rest.len() - remainder.len()]),
+        }
+
+        rest = remainder;
+    }
+
+    expanded.push_str(rest);
+
+    expanded
+}
+
+/// "bunqyy", followed by the hostname when it's known
+fn default_device_description() -> String {
+    let hostname = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty());
+
+    match hostname {
+        Some(hostname) => format!("bunqyy on {}", hostname),
+        None => "bunqyy".to_string(),
+    }
+}
+
+fn home_dir() -> Option<String> {
+    std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .ok()
+}
+
+/// The most items bunq returns for a single request
+pub(crate) const MAX_LIST_COUNT: u32 = 200;
+
+/// Which part of a listing to get. bunq lists newest first, `older_id` and `newer_id` limit
+/// it to the items before or after the item with that id. Without a count bunq's maximum
+/// of 200 is used.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ListParams {
+    pub count: Option<u32>,
+    pub older_id: Option<u64>,
+    pub newer_id: Option<u64>,
+}
+
+impl ListParams {
+    pub fn with_count(count: u32) -> Self {
+        ListParams {
+            count: Some(count),
+            ..Default::default()
+        }
+    }
+
+    /// Add the parameters to the query of a url. bunq accepts a count of 1 up to and including
+    /// 200, other counts are rejected up front.
+    pub fn append_to(&self, url: &mut Url) -> Result<(), BunqyyError> {
+        let count = validate_count(self.count.unwrap_or(MAX_LIST_COUNT))?;
+
+        let mut query = url.query_pairs_mut();
+        query.append_pair("count", &count.to_string());
+
+        if let Some(older_id) = self.older_id {
+            query.append_pair("older_id", &older_id.to_string());
+        }
+
+        if let Some(newer_id) = self.newer_id {
+            query.append_pair("newer_id", &newer_id.to_string());
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn validate_count(count: u32) -> Result<u32, BunqyyError> {
+    match count {
+        1..=MAX_LIST_COUNT => Ok(count),
+        _ => Err(BunqyyError::InvalidCount(count)),
+    }
+}
+
+/// The url of a listing, with the list parameters or the defaults when there are none
+#[allow(dead_code)]
+pub(crate) fn list_url(url: &str, list_params: Option<ListParams>) -> anyhow::Result<Url> {
+    let mut url = Url::parse(url)?;
+    list_params.unwrap_or_default().append_to(&mut url)?;
+
+    Ok(url)
 }
 
 /// Test
@@ -74,6 +543,104 @@ impl SetupContext {
 mod tests {
     use super::*;
 
+    #[test]
+    fn count_is_validated() {
+        assert_eq!(validate_count(1).unwrap(), 1);
+        assert_eq!(validate_count(200).unwrap(), 200);
+        assert!(matches!(
+            validate_count(0),
+            Err(BunqyyError::InvalidCount(0))
+        ));
+        assert!(matches!(
+            validate_count(201),
+            Err(BunqyyError::InvalidCount(201))
+        ));
+    }
+
+    #[test]
+    fn list_params_are_appended_to_the_query() {
+        let url = list_url("https://bunq.test/v1/user/1/event", None).unwrap();
+        assert_eq!(url.query(), Some("count=200"));
+
+        let list_params = ListParams {
+            count: Some(10),
+            older_id: Some(42),
+            newer_id: None,
+        };
+        let url = list_url(
+            "https://bunq.test/v1/user/1/event?display_user_event=false",
+            Some(list_params),
+        )
+        .unwrap();
+        assert_eq!(
+            url.query(),
+            Some("display_user_event=false&count=10&older_id=42")
+        );
+
+        assert!(list_url(
+            "https://bunq.test/v1/user/1/event",
+            Some(ListParams::with_count(0))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn aliases_are_found_by_type() {
+        let aliases: Vec<Alias> = serde_json::from_str(
+            r#"[
+                { "type": "PHONE_NUMBER", "value": "+31600000000", "name": "Me" },
+                { "type": "EMAIL", "value": "me@example.com", "name": "Me" },
+                { "type": "URL", "value": "https://bunq.me/me", "name": null },
+                { "type": "IBAN", "value": "NL00BUNQ0000000001", "name": "Me" }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(aliases[2].r#type, AliasType::Other);
+        assert_eq!(aliases.find_iban().unwrap().value, "NL00BUNQ0000000001");
+        assert_eq!(aliases.find_email().unwrap().value, "me@example.com");
+        assert!(aliases[..1].find_email().is_none());
+    }
+
+    #[test]
+    fn generated_idempotency_keys_are_unique() {
+        let first = IdempotencyKey::new();
+        let second = IdempotencyKey::new();
+
+        assert_eq!(first.as_str().len(), 32);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn storage_path_expands_home_directory() {
+        let home = home_dir().unwrap();
+
+        assert_eq!(
+            expand_path("~/.config/bunqyy/context.json"),
+            format!("{}/.config/bunqyy/context.json", home)
+        );
+        assert_eq!(expand_path("~other/context.json"), "~other/context.json");
+    }
+
+    #[test]
+    fn storage_path_expands_environment_variables() {
+        std::env::set_var("BUNQYY_TEST_STORAGE_DIR", "/var/lib/bunqyy");
+
+        assert_eq!(
+            expand_path("$BUNQYY_TEST_STORAGE_DIR/context.json"),
+            "/var/lib/bunqyy/context.json"
+        );
+        assert_eq!(
+            expand_path("${BUNQYY_TEST_STORAGE_DIR}.d/context.json"),
+            "/var/lib/bunqyy.d/context.json"
+        );
+        assert_eq!(
+            expand_path("$BUNQYY_TEST_UNSET_VARIABLE/context.json"),
+            "$BUNQYY_TEST_UNSET_VARIABLE/context.json"
+        );
+        assert_eq!(expand_path("${unterminated/x"), "${unterminated/x");
+    }
+
     #[test]
     fn test_setup_context() {
         let setup_context = SetupContext::new(
@@ -87,5 +654,69 @@ mod tests {
         assert_eq!(setup_context.client_id, "client_id");
         assert_eq!(setup_context.client_secret, "client_secret");
         assert_eq!(setup_context.storage_path, ".context.json");
+        assert_eq!(setup_context.api_base_url(), BUNQ_PRODUCTION_BASE_URL);
+        assert!(setup_context.device_description.starts_with("bunqyy"));
+    }
+
+    #[test]
+    fn setup_context_base_url_can_be_overridden() {
+        let setup_context = SetupContext::new(
+            Environment::PRODUCTION,
+            "client_id".to_string(),
+            "client_secret".to_string(),
+            ".context.json".to_string(),
+        )
+        .with_base_url("http://127.0.0.1:8080/v1")
+        .with_oauth_base_url("http://127.0.0.1:8080/oauth")
+        .with_device_description("bunqyy staging");
+
+        assert_eq!(setup_context.api_base_url(), "http://127.0.0.1:8080/v1");
+        assert_eq!(
+            setup_context.oauth_base_url.as_deref(),
+            Some("http://127.0.0.1:8080/oauth")
+        );
+        assert_eq!(setup_context.device_description, "bunqyy staging");
+    }
+
+    #[test]
+    fn profiles_are_stored_per_environment() {
+        let profiles = SetupProfiles::new("/tmp/bunqyy/.context.json".to_string())
+            .with_profile(Environment::SANDBOX, "sandbox_id", "sandbox_secret")
+            .with_profile(
+                Environment::PRODUCTION,
+                "production_id",
+                "production_secret",
+            );
+
+        let sandbox = profiles.for_environment(Environment::SANDBOX).unwrap();
+        assert_eq!(sandbox.client_id, "sandbox_id");
+        assert_eq!(sandbox.storage_path, "/tmp/bunqyy/.context.sandbox.json");
+        assert_eq!(sandbox.api_base_url(), BUNQ_SANDBOX_BASE_URL);
+
+        let production = profiles.for_environment(Environment::PRODUCTION).unwrap();
+        assert_eq!(production.client_secret, "production_secret");
+        assert_eq!(
+            production.storage_path,
+            "/tmp/bunqyy/.context.production.json"
+        );
+
+        assert_eq!(
+            storage_path_for_environment("context", Environment::SANDBOX),
+            "context.sandbox"
+        );
+    }
+
+    #[test]
+    fn missing_profile_is_reported() {
+        let profiles = SetupProfiles::new(".context.json".to_string()).with_profile(
+            Environment::SANDBOX,
+            "sandbox_id",
+            "sandbox_secret",
+        );
+
+        assert!(matches!(
+            profiles.for_environment(Environment::PRODUCTION),
+            Err(BunqyyError::MissingProfile(Environment::PRODUCTION))
+        ));
     }
 }
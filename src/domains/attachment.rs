@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::api_context::ManagedApiContext;
+use crate::common::{BunqyyError, IdempotencyKey};
+use crate::domains::monetary_account::MonetaryAccountId;
+use crate::http::{
+    get_authenticated_client, send_request, IdempotentRequestBuilder, WellKnownBunqHeaders,
+};
+
+/// The content types bunq accepts for attachments, by the file extensions they're inferred from.
+/// bunq accepts these four only, so they're listed here rather than looked up in a full mime
+/// database, which would still have to be checked against this list.
+const SUPPORTED_CONTENT_TYPES: [(&str, &[&str]); 4] = [
+    ("image/png", &["png"]),
+    ("image/jpeg", &["jpg", "jpeg"]),
+    ("image/gif", &["gif"]),
+    ("application/pdf", &["pdf"]),
+];
+
+/// Upload an attachment to a monetary account, e.g. to attach it to a payment as a receipt.
+/// bunq only accepts the content types PNG, JPEG, GIF and PDF.
+/// Pass an idempotency key to be able to safely retry the upload.
+pub async fn upload_attachment(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    content: Vec<u8>,
+    content_type: &str,
+    description: &str,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> anyhow::Result<AttachmentId> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/attachment",
+        base_url, user_id, monetary_account_id.0
+    );
+
+    let request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .header(
+            WellKnownBunqHeaders::AttachmentDescription.to_string(),
+            description,
+        )
+        .idempotency_key(idempotency_key)
+        .body(content);
+
+    #[derive(Deserialize, Debug)]
+    struct Content {
+        #[serde(rename = "Id")]
+        id: AttachmentId,
+    }
+
+    send_request::<Content>(request)
+        .await?
+        .into_iter()
+        .map(|content| content.id)
+        .next()
+        .ok_or(anyhow!("Id not found in response"))
+}
+
+/// Upload a file as attachment, inferring its content type from the extension and using
+/// its file name as description. Files of a type bunq doesn't accept are rejected up front.
+pub async fn upload_attachment_from_path(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    path: impl AsRef<Path>,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> anyhow::Result<AttachmentId> {
+    let path = path.as_ref();
+
+    let content_type = content_type_for_path(path)?;
+
+    let description = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let content = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read attachment {}", path.display()))?;
+
+    upload_attachment(
+        api_context,
+        monetary_account_id,
+        content,
+        content_type,
+        description.as_str(),
+        idempotency_key,
+    )
+    .await
+}
+
+/// Infer the content type of a file from its extension, limited to the types bunq accepts
+fn content_type_for_path(path: &Path) -> Result<&'static str, BunqyyError> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    SUPPORTED_CONTENT_TYPES
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+        .map(|(content_type, _)| *content_type)
+        .ok_or_else(|| {
+            BunqyyError::UnsupportedAttachmentType(
+                extension,
+                SUPPORTED_CONTENT_TYPES
+                    .iter()
+                    .map(|(content_type, _)| *content_type)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
+        })
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct AttachmentId {
+    pub id: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_type_is_inferred_from_extension() {
+        assert_eq!(
+            content_type_for_path(Path::new("receipts/lunch.JPG")).unwrap(),
+            "image/jpeg"
+        );
+        assert_eq!(
+            content_type_for_path(Path::new("invoice.pdf")).unwrap(),
+            "application/pdf"
+        );
+    }
+
+    #[test]
+    fn unsupported_extension_is_rejected() {
+        let error = content_type_for_path(Path::new("notes.txt")).unwrap_err();
+
+        assert!(error.to_string().contains("image/png"));
+        assert!(content_type_for_path(Path::new("no-extension")).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::api_context::ManagedApiContext;
+use crate::http::{get_authenticated_client, send_request, send_request_for_bytes};
+
+/// Get the image of an avatar, as referenced by users and accounts.
+/// bunq stores an avatar in several resolutions, the smallest one that's at least `size`
+/// pixels wide is returned, or the largest one when none is.
+pub async fn get_avatar(
+    api_context: &ManagedApiContext,
+    avatar_uuid: &str,
+    size: u32,
+) -> anyhow::Result<AvatarImage> {
+    let base_url = api_context.lock().await.base_url().to_owned();
+
+    let client = get_authenticated_client(api_context).await?;
+
+    #[derive(Deserialize, Debug)]
+    struct AvatarWrapper {
+        #[serde(rename = "Avatar")]
+        avatar: Avatar,
+    }
+
+    let avatar =
+        send_request::<AvatarWrapper>(client.get(format!("{}/avatar/{}", base_url, avatar_uuid)))
+            .await?
+            .into_iter()
+            .map(|entry| entry.avatar)
+            .next()
+            .ok_or(anyhow!("Avatar not found in response"))?;
+
+    let image = avatar
+        .image_for_size(size)
+        .ok_or(anyhow!("Avatar {} has no images", avatar_uuid))?;
+
+    let content = send_request_for_bytes(client.get(format!(
+        "{}/attachment-public/{}/content",
+        base_url, image.attachment_public_uuid
+    )))
+    .await?;
+
+    Ok(AvatarImage {
+        content_type: image.content_type.clone(),
+        content,
+    })
+}
+
+/// The picture of a user or account
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct Avatar {
+    pub uuid: String,
+    /// The same picture in different resolutions
+    #[serde(default)]
+    pub image: Vec<Image>,
+}
+
+impl Avatar {
+    fn image_for_size(&self, size: u32) -> Option<&Image> {
+        self.image
+            .iter()
+            .filter(|image| image.width >= size)
+            .min_by_key(|image| image.width)
+            .or_else(|| self.image.iter().max_by_key(|image| image.width))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct Image {
+    pub attachment_public_uuid: String,
+    pub content_type: String,
+    pub height: u32,
+    pub width: u32,
+}
+
+/// The content of an avatar in a single resolution
+#[derive(Debug, Clone)]
+pub struct AvatarImage {
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_closest_to_size_is_picked() {
+        let image = |width: u32| Image {
+            attachment_public_uuid: format!("image-{}", width),
+            content_type: "image/png".to_string(),
+            height: width,
+            width,
+        };
+        let avatar = Avatar {
+            uuid: "avatar".to_string(),
+            image: vec![image(1024), image(128), image(512)],
+        };
+
+        assert_eq!(avatar.image_for_size(100).unwrap().width, 128);
+        assert_eq!(avatar.image_for_size(512).unwrap().width, 512);
+        assert_eq!(avatar.image_for_size(2048).unwrap().width, 1024);
+        assert!(Avatar {
+            uuid: "empty".to_string(),
+            image: vec![],
+        }
+        .image_for_size(128)
+        .is_none());
+    }
+}
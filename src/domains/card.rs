@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::api_context::ManagedApiContext;
+use crate::domains::monetary_account::Amount;
+use crate::http::{get_authenticated_client, send_request};
+
+/// Get the spending limits of a card
+pub async fn get_card_limits(
+    api_context: &ManagedApiContext,
+    card_id: CardId,
+) -> anyhow::Result<CardLimits> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!("{}/user/{}/card/{}", base_url, user_id, card_id.0);
+
+    // bunq wraps the card in its kind, e.g. `CardDebit` or `CardCredit`
+    send_request::<HashMap<String, CardLimits>>(client.get(url))
+        .await?
+        .into_iter()
+        .flat_map(|card| card.into_values())
+        .next()
+        .ok_or(anyhow!("Card not found in response"))
+}
+
+/// Set the spending limits of a card, limits that are `None` are left as they are.
+/// Returns the limits as they are after the update.
+pub async fn set_card_limits(
+    api_context: &ManagedApiContext,
+    card_id: CardId,
+    limits: CardLimits,
+) -> anyhow::Result<CardLimits> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!("{}/user/{}/card/{}", base_url, user_id, card_id.0);
+
+    let body = serde_json::to_string(&limits)?;
+
+    send_request::<serde_json::Value>(client.put(url).body(body)).await?;
+
+    get_card_limits(api_context, card_id).await
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CardId(pub u64);
+
+/// The daily limits of a card. bunq has one limit for spending in shops, in person as well as
+/// online and contactless, and a separate one for withdrawing cash at ATMs.
+/// Only the limits are taken from the card, its other fields are left out.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CardLimits {
+    /// The most that can be spent with the card per day
+    #[serde(
+        rename = "card_limit",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub spending: Option<Amount>,
+    /// The most that can be withdrawn at ATMs per day
+    #[serde(
+        rename = "card_limit_atm",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub atm: Option<Amount>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    use crate::http::{process_response_content, BunqResponse};
+
+    #[test]
+    fn limits_are_taken_from_any_kind_of_card() {
+        let response = r#"
+        {
+            "Response": [
+                {
+                    "CardDebit": {
+                        "id": 9,
+                        "status": "ACTIVE",
+                        "card_limit": { "currency": "EUR", "value": "500.00" },
+                        "card_limit_atm": { "currency": "EUR", "value": "250.00" }
+                    }
+                }
+            ]
+        }
+        "#;
+
+        let limits =
+            match process_response_content::<HashMap<String, CardLimits>>(response).unwrap() {
+                BunqResponse::Success(content) => content
+                    .response
+                    .into_iter()
+                    .flat_map(|card| card.into_values())
+                    .next()
+                    .unwrap(),
+                BunqResponse::Error(_) => panic!("Expected success"),
+            };
+
+        assert_eq!(limits.spending.unwrap().value.to_string(), "500.00");
+        assert_eq!(limits.atm.unwrap().value.to_string(), "250.00");
+    }
+
+    #[test]
+    fn only_limits_that_are_set_are_sent() {
+        let limits = CardLimits {
+            atm: Some(Amount::new(Decimal::new(10000, 2), "EUR")),
+            ..CardLimits::default()
+        };
+
+        assert_eq!(
+            serde_json::to_string(&limits).unwrap(),
+            r#"{"card_limit_atm":{"currency":"EUR","value":"100.00"}}"#
+        );
+    }
+}
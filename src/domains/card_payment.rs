@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::api_context::ManagedApiContext;
+use crate::bunq_timestamp;
+use crate::common::{list_url, ListParams};
+use crate::domains::monetary_account::{Amount, MonetaryAccountId};
+use crate::domains::payment::LabelMonetaryAccount;
+use crate::domains::transaction::Transaction;
+use crate::http::{get_authenticated_client, send_request};
+
+/// Get the payments made with the cards of an account, which bunq calls mastercard actions
+pub async fn get_card_payments(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    list_params: Option<ListParams>,
+) -> anyhow::Result<Vec<CardPayment>> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = list_url(
+        &format!(
+            "{}/user/{}/monetary-account/{}/mastercard-action",
+            base_url, user_id, monetary_account_id.0
+        ),
+        list_params,
+    )?;
+
+    #[derive(Deserialize, Debug)]
+    struct CardPaymentWrapper {
+        #[serde(rename = "MasterCardAction")]
+        card_payment: CardPayment,
+    }
+
+    Ok(send_request::<CardPaymentWrapper>(client.get(url))
+        .await?
+        .into_iter()
+        .map(|entry| entry.card_payment)
+        .collect())
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct CardPaymentId(pub u64);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct CardPayment {
+    pub id: CardPaymentId,
+    #[serde(with = "bunq_timestamp")]
+    pub created: DateTime<Utc>,
+    pub monetary_account_id: MonetaryAccountId,
+    /// The amount in the currency of the account
+    pub amount_billing: Amount,
+    /// The amount in the currency of the merchant
+    pub amount_local: Amount,
+    pub counterparty_alias: LabelMonetaryAccount,
+    pub description: String,
+    /// Whether the payment was allowed, e.g. `ALLOWED` or `INSUFFICIENT_BALANCE`
+    pub decision: String,
+    pub city: Option<String>,
+    /// The merchant category code (MCC) as assigned by the card network
+    pub merchant_category_code: Option<String>,
+}
+
+impl CardPayment {
+    /// The category of the merchant, decoded from its merchant category code
+    pub fn merchant_category(&self) -> Option<MerchantCategory> {
+        self.merchant_category_code
+            .as_deref()
+            .and_then(|code| code.trim().parse::<u16>().ok())
+            .map(MerchantCategory::from_code)
+    }
+}
+
+impl Transaction for CardPayment {
+    fn amount(&self) -> &Amount {
+        &self.amount_billing
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    fn counterparty_name(&self) -> &str {
+        &self.counterparty_alias.display_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// A coarse, human-readable grouping of merchant category codes, e.g. for budgeting
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MerchantCategory {
+    Travel,
+    Transport,
+    Utilities,
+    Groceries,
+    Restaurants,
+    Fuel,
+    Health,
+    Shopping,
+    Entertainment,
+    CashWithdrawal,
+    Other,
+}
+
+impl MerchantCategory {
+    /// Decode a merchant category code into the category it belongs to
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            3000..=3999 | 4411 | 4511 | 4722 | 7011 | 7512 => MerchantCategory::Travel,
+            4111..=4131 | 4784 | 4789 | 7523 => MerchantCategory::Transport,
+            4812..=4900 => MerchantCategory::Utilities,
+            5411 | 5422 | 5441 | 5451 | 5462 | 5499 => MerchantCategory::Groceries,
+            5811..=5814 => MerchantCategory::Restaurants,
+            5541 | 5542 | 5983 => MerchantCategory::Fuel,
+            5122 | 5912 | 8011..=8099 => MerchantCategory::Health,
+            5200..=5399 | 5600..=5699 | 5900..=5999 => MerchantCategory::Shopping,
+            7832 | 7841 | 7900..=7999 => MerchantCategory::Entertainment,
+            6010 | 6011 => MerchantCategory::CashWithdrawal,
+            _ => MerchantCategory::Other,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MerchantCategory::Travel => "Travel",
+            MerchantCategory::Transport => "Transport",
+            MerchantCategory::Utilities => "Utilities",
+            MerchantCategory::Groceries => "Groceries",
+            MerchantCategory::Restaurants => "Restaurants",
+            MerchantCategory::Fuel => "Fuel",
+            MerchantCategory::Health => "Health",
+            MerchantCategory::Shopping => "Shopping",
+            MerchantCategory::Entertainment => "Entertainment",
+            MerchantCategory::CashWithdrawal => "Cash withdrawal",
+            MerchantCategory::Other => "Other",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{process_response_content, BunqResponse};
+
+    #[test]
+    fn merchant_category_codes_are_decoded() {
+        assert_eq!(
+            MerchantCategory::from_code(5411),
+            MerchantCategory::Groceries
+        );
+        assert_eq!(
+            MerchantCategory::from_code(5812),
+            MerchantCategory::Restaurants
+        );
+        assert_eq!(MerchantCategory::from_code(3058), MerchantCategory::Travel);
+        assert_eq!(
+            MerchantCategory::from_code(6011),
+            MerchantCategory::CashWithdrawal
+        );
+        assert_eq!(MerchantCategory::from_code(1), MerchantCategory::Other);
+    }
+
+    #[test]
+    fn card_payment_exposes_merchant_category() {
+        let response = r#"
+        {
+            "Response": [
+                {
+                    "id": 1,
+                    "created": "2024-10-14 21:19:14.426211",
+                    "monetary_account_id": 7,
+                    "amount_billing": { "currency": "EUR", "value": "-4.50" },
+                    "amount_local": { "currency": "EUR", "value": "-4.50" },
+                    "counterparty_alias": { "iban": null, "display_name": "Bakery", "country": "NL" },
+                    "description": "Bakery Amsterdam",
+                    "decision": "ALLOWED",
+                    "city": "Amsterdam",
+                    "merchant_category_code": "5462"
+                }
+            ]
+        }
+        "#;
+
+        let card_payment = match process_response_content::<CardPayment>(response).unwrap() {
+            BunqResponse::Success(content) => content.response.into_iter().next().unwrap(),
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        assert_eq!(
+            card_payment.merchant_category(),
+            Some(MerchantCategory::Groceries)
+        );
+        assert_eq!(card_payment.counterparty_name(), "Bakery");
+    }
+}
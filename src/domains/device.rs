@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::api_context::ManagedApiContext;
+use crate::bunq_timestamp;
+use crate::common::{list_url, ListParams};
+use crate::http::{get_authenticated_client, send_request};
+
+/// Get the devices that are registered for the user, e.g. phones and servers such as this one
+pub async fn get_devices(
+    api_context: &ManagedApiContext,
+    list_params: Option<ListParams>,
+) -> anyhow::Result<Vec<Device>> {
+    let base_url = api_context.lock().await.base_url().to_owned();
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = list_url(&format!("{}/device", base_url), list_params)?;
+
+    // bunq wraps each device in its kind, e.g. `DeviceServer` or `DevicePhone`
+    Ok(send_request::<HashMap<String, Device>>(client.get(url))
+        .await?
+        .into_iter()
+        .flat_map(|device| device.into_values())
+        .collect())
+}
+
+/// Get the device the api context was set up with, as bunq currently knows it. Useful to
+/// confirm the device is still active and hasn't been blocked in the meantime.
+pub async fn get_current_device(api_context: &ManagedApiContext) -> anyhow::Result<Device> {
+    let device_id = api_context.lock().await.device_id.ok_or(anyhow!(
+        "The api context doesn't know its device, it was stored before the device was kept"
+    ))?;
+
+    find_device(get_devices(api_context, None).await?, device_id).ok_or(anyhow!(
+        "Device {} is no longer registered at bunq",
+        device_id
+    ))
+}
+
+fn find_device(devices: Vec<Device>, device_id: u64) -> Option<Device> {
+    devices.into_iter().find(|device| device.id == device_id)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct Device {
+    pub id: u64,
+    #[serde(with = "bunq_timestamp")]
+    pub created: DateTime<Utc>,
+    #[serde(with = "bunq_timestamp")]
+    pub updated: DateTime<Utc>,
+    pub description: String,
+    /// The ip address the device is permitted to make requests from, if it's bound to one
+    #[serde(default)]
+    pub ip: Option<String>,
+    pub status: DeviceStatus,
+}
+
+impl Device {
+    pub fn is_active(&self) -> bool {
+        self.status == DeviceStatus::Active
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DeviceStatus {
+    #[serde(alias = "ACTIVE")]
+    Active,
+    #[serde(alias = "BLOCKED")]
+    Blocked,
+    #[serde(alias = "NEEDS_CONFIRMATION")]
+    NeedsConfirmation,
+    #[serde(alias = "OBSOLETE")]
+    Obsolete,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{process_response_content, BunqResponse};
+
+    #[test]
+    fn current_device_is_found_among_any_kind_of_device() {
+        let response = r#"
+        {
+            "Response": [
+                {
+                    "DevicePhone": {
+                        "id": 1,
+                        "created": "2024-10-14 21:19:14.426211",
+                        "updated": "2024-10-14 21:19:14.426211",
+                        "description": "Phone",
+                        "ip": null,
+                        "status": "ACTIVE"
+                    }
+                },
+                {
+                    "DeviceServer": {
+                        "id": 2,
+                        "created": "2024-10-15 08:00:00.000000",
+                        "updated": "2024-10-16 08:00:00.000000",
+                        "description": "bunqyy on server",
+                        "ip": "192.0.2.1",
+                        "status": "BLOCKED"
+                    }
+                }
+            ]
+        }
+        "#;
+
+        let devices = match process_response_content::<HashMap<String, Device>>(response).unwrap() {
+            BunqResponse::Success(content) => content
+                .response
+                .into_iter()
+                .flat_map(|device| device.into_values())
+                .collect::<Vec<_>>(),
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        let device = find_device(devices.clone(), 2).unwrap();
+        assert_eq!(device.description, "bunqyy on server");
+        assert_eq!(device.ip.as_deref(), Some("192.0.2.1"));
+        assert!(!device.is_active());
+
+        assert!(find_device(devices, 3).is_none());
+    }
+}
@@ -0,0 +1,393 @@
+use std::cmp::Reverse;
+use std::future::Future;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api_context::ManagedApiContext;
+use crate::bunq_timestamp;
+use crate::common::{list_url, validate_count, ListParams, MAX_LIST_COUNT};
+use crate::domains::monetary_account::{Amount, MonetaryAccountId};
+use crate::http::{get_authenticated_client, send_request};
+
+/// Get the most recent events of the user, across all of its accounts
+pub async fn get_events(
+    api_context: &ManagedApiContext,
+    list_params: Option<ListParams>,
+) -> anyhow::Result<Vec<Event>> {
+    fetch_events(api_context, None, list_params).await
+}
+
+/// Get the most recent events of a single account, of the given types only.
+/// bunq filters on the account itself, but can't filter on type. Events are fetched page by
+/// page until the count of the list params is reached with events of the given types, or
+/// until there are no older events.
+/// An empty list of types returns events of every type.
+pub async fn get_events_filtered(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    event_types: &[EventType],
+    list_params: Option<ListParams>,
+) -> anyhow::Result<Vec<Event>> {
+    if event_types.is_empty() {
+        return fetch_events(api_context, Some(monetary_account_id), list_params).await;
+    }
+
+    collect_of_types(
+        |page_params| fetch_events(api_context, Some(monetary_account_id), Some(page_params)),
+        event_types,
+        list_params.unwrap_or_default(),
+    )
+    .await
+}
+
+/// Get the most recent payments, card payments and requests of an account as one timeline,
+/// newest first. Events of other types are left out.
+pub async fn get_timeline(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    count: u32,
+) -> anyhow::Result<Vec<TimelineEntry>> {
+    let events = get_events_filtered(
+        api_context,
+        monetary_account_id,
+        &[
+            EventType::Payment,
+            EventType::CardPayment,
+            EventType::Request,
+        ],
+        Some(ListParams::with_count(count)),
+    )
+    .await?;
+
+    Ok(timeline(&events))
+}
+
+fn timeline(events: &[Event]) -> Vec<TimelineEntry> {
+    let mut entries: Vec<TimelineEntry> = events.iter().map(TimelineEntry::from_event).collect();
+
+    entries.sort_by_key(|entry| Reverse(entry.at));
+
+    entries
+}
+
+async fn fetch_events(
+    api_context: &ManagedApiContext,
+    monetary_account_id: Option<MonetaryAccountId>,
+    list_params: Option<ListParams>,
+) -> anyhow::Result<Vec<Event>> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let mut url = list_url(&format!("{}/user/{}/event", base_url, user_id), list_params)?;
+    if let Some(monetary_account_id) = monetary_account_id {
+        url.query_pairs_mut()
+            .append_pair("monetary_account_id", &monetary_account_id.0.to_string());
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct EventWrapper {
+        #[serde(rename = "Event")]
+        event: Event,
+    }
+
+    Ok(send_request::<EventWrapper>(client.get(url))
+        .await?
+        .into_iter()
+        .map(|entry| entry.event)
+        .collect())
+}
+
+/// Fetch pages of the maximum size, each older than the one before, until `count` events of
+/// the types are found or a page comes back short, which means the feed ended
+async fn collect_of_types<F, Fut>(
+    mut fetch_page: F,
+    event_types: &[EventType],
+    list_params: ListParams,
+) -> anyhow::Result<Vec<Event>>
+where
+    F: FnMut(ListParams) -> Fut,
+    Fut: Future<Output = anyhow::Result<Vec<Event>>>,
+{
+    let count = validate_count(list_params.count.unwrap_or(MAX_LIST_COUNT))? as usize;
+
+    let mut page_params = ListParams {
+        count: Some(MAX_LIST_COUNT),
+        ..list_params
+    };
+    let mut events = vec![];
+
+    loop {
+        let page = fetch_page(page_params).await?;
+        let is_last_page = page.len() < MAX_LIST_COUNT as usize;
+
+        page_params.older_id = page.last().map(|event| event.id.0);
+        events.extend(only_of_types(page, event_types));
+
+        if events.len() >= count || is_last_page {
+            break;
+        }
+    }
+
+    events.truncate(count);
+
+    Ok(events)
+}
+
+fn only_of_types(events: Vec<Event>, event_types: &[EventType]) -> Vec<Event> {
+    if event_types.is_empty() {
+        return events;
+    }
+
+    events
+        .into_iter()
+        .filter(|event| event_types.contains(&event.event_type()))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct EventId(pub u64);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct Event {
+    pub id: EventId,
+    #[serde(with = "bunq_timestamp")]
+    pub created: DateTime<Utc>,
+    pub action: String,
+    pub monetary_account_id: Option<MonetaryAccountId>,
+    pub status: Option<String>,
+    /// The object the event is about, keyed by its type, e.g. `{"Payment": {..}}`
+    pub object: Value,
+}
+
+impl Event {
+    /// The type of the object the event is about
+    pub fn event_type(&self) -> EventType {
+        self.object
+            .as_object()
+            .and_then(|object| object.keys().next())
+            .map(|key| EventType::from_object_key(key))
+            .unwrap_or(EventType::Other)
+    }
+}
+
+/// An event on the timeline of an account, with the details that payments, card payments and
+/// requests have in common
+#[derive(Debug, Clone)]
+pub struct TimelineEntry {
+    pub event_id: EventId,
+    pub at: DateTime<Utc>,
+    pub kind: EventType,
+    pub amount: Option<Amount>,
+    /// The name of the other party, when bunq knows it
+    pub counterparty: Option<String>,
+    pub description: Option<String>,
+}
+
+impl TimelineEntry {
+    /// Take the common details from the object of the event, which differ in name per type
+    fn from_event(event: &Event) -> Self {
+        let object = event
+            .object
+            .as_object()
+            .and_then(|object| object.values().next())
+            .unwrap_or(&Value::Null);
+
+        let amount = ["amount", "amount_billing", "amount_inquired"]
+            .iter()
+            .find_map(|field| serde_json::from_value::<Amount>(object[*field].clone()).ok());
+
+        let text = |value: &Value| {
+            value
+                .as_str()
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        };
+
+        TimelineEntry {
+            event_id: event.id,
+            at: event.created,
+            kind: event.event_type(),
+            amount,
+            counterparty: text(&object["counterparty_alias"]["display_name"]),
+            description: text(&object["description"]),
+        }
+    }
+}
+
+/// The types of events that can be filtered on
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EventType {
+    Payment,
+    /// Requests for money, both made and received
+    Request,
+    CardPayment,
+    Other,
+}
+
+impl EventType {
+    fn from_object_key(key: &str) -> Self {
+        match key {
+            "Payment" | "PaymentBatch" => EventType::Payment,
+            "RequestInquiry" | "RequestInquiryBatch" | "RequestResponse" => EventType::Request,
+            "MasterCardAction" => EventType::CardPayment,
+            _ => EventType::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: u64, object_key: &str) -> Event {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "created": "2024-10-14 21:19:14.426211",
+            "action": "CREATE",
+            "monetary_account_id": 7,
+            "status": "FINALIZED",
+            "object": { object_key: { "id": 1 } }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn event_type_is_taken_from_the_object() {
+        assert_eq!(event(1, "Payment").event_type(), EventType::Payment);
+        assert_eq!(event(2, "RequestResponse").event_type(), EventType::Request);
+        assert_eq!(
+            event(3, "MasterCardAction").event_type(),
+            EventType::CardPayment
+        );
+        assert_eq!(
+            event(4, "ShareInviteBankInquiry").event_type(),
+            EventType::Other
+        );
+    }
+
+    #[test]
+    fn timeline_is_ordered_newest_first() {
+        let events: Vec<Event> = serde_json::from_value(serde_json::json!([
+            {
+                "id": 1,
+                "created": "2024-10-14 09:00:00.000000",
+                "action": "CREATE",
+                "monetary_account_id": 7,
+                "status": "FINALIZED",
+                "object": { "Payment": {
+                    "amount": { "currency": "EUR", "value": "-30.00" },
+                    "counterparty_alias": { "display_name": "Restaurant" },
+                    "description": "Dinner"
+                } }
+            },
+            {
+                "id": 2,
+                "created": "2024-10-15 09:00:00.000000",
+                "action": "CREATE",
+                "monetary_account_id": 7,
+                "status": "FINALIZED",
+                "object": { "MasterCardAction": {
+                    "amount_billing": { "currency": "EUR", "value": "-4.50" },
+                    "counterparty_alias": { "display_name": "Bakery" },
+                    "description": "Bakery Amsterdam"
+                } }
+            }
+        ]))
+        .unwrap();
+
+        let entries = timeline(&events);
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.event_id.0)
+                .collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        assert_eq!(entries[0].kind, EventType::CardPayment);
+        assert_eq!(
+            entries[0].amount.as_ref().unwrap().value.to_string(),
+            "-4.50"
+        );
+        assert_eq!(entries[0].counterparty.as_deref(), Some("Bakery"));
+        assert_eq!(entries[1].description.as_deref(), Some("Dinner"));
+    }
+
+    #[test]
+    fn events_are_filtered_by_type() {
+        let events = vec![
+            event(1, "Payment"),
+            event(2, "RequestInquiry"),
+            event(3, "MasterCardAction"),
+        ];
+
+        let filtered = only_of_types(events.clone(), &[EventType::Payment, EventType::Request]);
+        assert_eq!(
+            filtered.iter().map(|event| event.id.0).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        assert_eq!(only_of_types(events, &[]).len(), 3);
+    }
+
+    /// A feed of 450 events, newest first, in which every tenth event is a payment
+    async fn feed_page(list_params: ListParams) -> anyhow::Result<Vec<Event>> {
+        let older_than = list_params.older_id.unwrap_or(451);
+        let count = list_params.count.unwrap() as u64;
+
+        Ok((1..older_than)
+            .rev()
+            .take(count as usize)
+            .map(|id| match id % 10 {
+                0 => event(id, "Payment"),
+                _ => event(id, "MasterCardAction"),
+            })
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn filtered_events_are_paged_until_the_count_is_reached() {
+        let pages = std::sync::Mutex::new(vec![]);
+        let fetch_page = |list_params: ListParams| {
+            pages.lock().unwrap().push(list_params.older_id);
+            feed_page(list_params)
+        };
+
+        let events = collect_of_types(
+            fetch_page,
+            &[EventType::Payment],
+            ListParams::with_count(25),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(events.len(), 25);
+        assert_eq!(events[0].id.0, 450);
+        assert_eq!(events[24].id.0, 210);
+        assert_eq!(*pages.lock().unwrap(), vec![None, Some(251)]);
+    }
+
+    #[tokio::test]
+    async fn filtered_events_stop_at_the_end_of_the_feed() {
+        let events = collect_of_types(
+            feed_page,
+            &[EventType::Payment],
+            ListParams::with_count(100),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(events.len(), 45);
+        assert_eq!(events[44].id.0, 10);
+    }
+}
@@ -1,6 +1,27 @@
+#[cfg(feature = "attachments")]
+pub mod attachment;
+pub mod avatar;
+#[cfg(feature = "cards")]
+pub mod card;
+#[cfg(feature = "card-payments")]
+pub mod card_payment;
+pub mod device;
+#[cfg(feature = "events")]
+pub mod event;
 #[cfg(feature = "monetary-accounts")]
 pub mod monetary_account;
-pub(crate) mod oauth;
+#[cfg(feature = "notifications")]
+pub mod notification_filter;
+pub mod oauth;
 
 #[cfg(feature = "payments")]
 pub mod payment;
+#[cfg(feature = "requests")]
+pub mod request;
+#[cfg(feature = "statements")]
+pub mod statement;
+#[cfg(feature = "tabs")]
+pub mod tab;
+#[cfg(feature = "monetary-accounts")]
+pub mod transaction;
+pub mod user;
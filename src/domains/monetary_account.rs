@@ -1,44 +1,296 @@
-use anyhow::Context;
-use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use anyhow::{anyhow, Context};
+use rust_decimal::Decimal;
+use serde::de::Error;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
 
 use crate::api_context::ManagedApiContext;
-use crate::common::BUNQ_BASE_URL;
-use crate::http::{get_authenticated_client, process_response_content, BunqResponse};
+use crate::common::{Alias, BunqyyError, FindAlias};
+use crate::domains::avatar::Avatar;
+use crate::http::{deserialize_entries, get_authenticated_client, send_request};
+use crate::serde_helpers::deserialize_string_number;
 
 pub async fn get_monetary_accounts(
     api_context: &ManagedApiContext,
 ) -> anyhow::Result<Vec<MonetaryAccount>> {
-    let user_id = api_context.lock().await.session_context.user_id;
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
 
     let client = get_authenticated_client(api_context).await?;
 
-    let response_result = client
-        .get(format!(
-            "{}/user/{}/monetary-account",
-            BUNQ_BASE_URL, user_id
-        ))
-        .send()
+    let entries = send_request::<Value>(
+        client.get(format!("{}/user/{}/monetary-account", base_url, user_id)),
+    )
+    .await?;
+
+    // An account of a kind that's not modelled and lacks some of the common fields is left
+    // out, rather than failing to list all the others
+    Ok(deserialize_entries::<MonetaryAccount>(entries))
+}
+
+/// Get a single monetary account, of any kind. Kinds that aren't modelled are returned as
+/// [`MonetaryAccount::Unknown`] rather than failing.
+pub async fn get_monetary_account(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<MonetaryAccount> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    send_request::<MonetaryAccount>(client.get(format!(
+        "{}/user/{}/monetary-account/{}",
+        base_url, user_id, monetary_account_id.0
+    )))
+    .await
+    .with_context(|| format!("Cannot get monetary account {}", monetary_account_id.0))?
+    .into_iter()
+    .next()
+    .ok_or(anyhow!(
+        "Monetary account {} not found in response",
+        monetary_account_id.0
+    ))
+}
+
+/// Get the balance of a single account, without fetching all of them
+pub async fn get_balance(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<Amount> {
+    Ok(get_monetary_account(api_context, monetary_account_id)
         .await?
-        .text()
-        .await?;
+        .get_balance()
+        .clone())
+}
 
-    let response = process_response_content::<MonetaryAccount>(response_result.as_str())
-        .with_context(|| "Failed to process response for monetary accounts".to_string())?;
+/// Get only the monetary accounts that are active, leaving out e.g. cancelled ones
+pub async fn get_active_monetary_accounts(
+    api_context: &ManagedApiContext,
+) -> anyhow::Result<Vec<MonetaryAccount>> {
+    Ok(only_active(get_monetary_accounts(api_context).await?))
+}
+
+pub(crate) fn only_active(accounts: Vec<MonetaryAccount>) -> Vec<MonetaryAccount> {
+    accounts
+        .into_iter()
+        .filter(|account| account.get_status() == &Status::Active)
+        .collect()
+}
+
+/// Get the IBANs of the user's own accounts, e.g. to recognize payments between them
+pub async fn get_own_ibans(api_context: &ManagedApiContext) -> anyhow::Result<Vec<OwnIban>> {
+    Ok(own_ibans(&get_monetary_accounts(api_context).await?))
+}
+
+pub(crate) fn own_ibans(accounts: &[MonetaryAccount]) -> Vec<OwnIban> {
+    accounts
+        .iter()
+        .filter_map(|account| {
+            account.get_iban().map(|iban| OwnIban {
+                monetary_account_id: account.get_id(),
+                iban: iban.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// An IBAN of one of the user's accounts
+#[derive(Debug, Clone)]
+pub struct OwnIban {
+    pub monetary_account_id: MonetaryAccountId,
+    pub iban: String,
+}
+
+/// Update the description and/or daily limit of a bank account, fields that are `None` are
+/// left as they are. Returns the account as it is after the update.
+pub async fn update_monetary_account(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    description: Option<&str>,
+    daily_limit: Option<Amount>,
+) -> anyhow::Result<MonetaryAccount> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
 
-    match response {
-        BunqResponse::Success(content) => Ok(content.response),
-        BunqResponse::Error(errors) => Err(anyhow::anyhow!("Error: {:?}", errors.error)),
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account-bank/{}",
+        base_url, user_id, monetary_account_id.0
+    );
+
+    #[derive(Serialize, Debug)]
+    struct Payload<'a> {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        daily_limit: Option<Amount>,
+    }
+
+    let body = serde_json::to_string(&Payload {
+        description,
+        daily_limit,
+    })?;
+
+    send_request::<serde_json::Value>(client.put(&url).body(body)).await?;
+
+    send_request::<MonetaryAccount>(client.get(&url))
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(anyhow!("Monetary account not found in response"))
+}
+
+/// Get the savings goal of a savings account, `None` when no goal is set
+pub async fn get_savings_goal(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<Option<SavingsGoal>> {
+    match get_monetary_account(api_context, monetary_account_id).await? {
+        MonetaryAccount::MonetaryAccountSavings(account) => Ok(account.get_savings_goal()),
+        _ => Err(anyhow!(
+            "Monetary account {} is not a savings account",
+            monetary_account_id.0
+        )),
+    }
+}
+
+/// Set the amount to save up to on a savings account. Returns the goal as it is after the
+/// update, along with the progress bunq calculated for it.
+pub async fn set_savings_goal(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    target: Amount,
+) -> anyhow::Result<SavingsGoal> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account-savings/{}",
+        base_url, user_id, monetary_account_id.0
+    );
+
+    #[derive(Serialize, Debug)]
+    struct Payload {
+        savings_goal: Amount,
     }
+
+    let body = serde_json::to_string(&Payload {
+        savings_goal: target,
+    })?;
+
+    send_request::<serde_json::Value>(client.put(&url).body(body)).await?;
+
+    get_savings_goal(api_context, monetary_account_id)
+        .await?
+        .ok_or(anyhow!("Savings goal not found after setting it"))
 }
 
 /// A monetary account wraps all kind of accounts in bunq
 /// Some simple accessors are provided to get the name, balance, id and status
-#[derive(Deserialize, Debug)]
+/// bunq wraps each account in its kind, e.g. `{"MonetaryAccountBank": {..}}`.
+/// Kinds that aren't modelled (yet) become [`MonetaryAccount::Unknown`] rather than an error,
+/// so new kinds of accounts at bunq don't break listing the others.
+#[derive(Debug, Clone)]
 pub enum MonetaryAccount {
     MonetaryAccountBank(MonetaryAccountBank),
     MonetaryAccountJoint(MonetaryAccountJoint),
     MonetaryAccountExternalSavings(MonetaryAccountExternalSavings),
     MonetaryAccountSavings(MonetaryAccountSavings),
+    Unknown(MonetaryAccountUnknown),
+}
+
+impl<'de> Deserialize<'de> for MonetaryAccount {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let tagged = HashMap::<String, Value>::deserialize(deserializer)?;
+
+        let mut entries = tagged.into_iter();
+        let (kind, content) = match (entries.next(), entries.next()) {
+            (Some(entry), None) => entry,
+            _ => {
+                return Err(D::Error::custom(
+                    "expected a single kind of monetary account",
+                ))
+            }
+        };
+
+        let account = match kind.as_str() {
+            "MonetaryAccountBank" => {
+                serde_json::from_value(content).map(MonetaryAccount::MonetaryAccountBank)
+            }
+            "MonetaryAccountJoint" => {
+                serde_json::from_value(content).map(MonetaryAccount::MonetaryAccountJoint)
+            }
+            "MonetaryAccountExternalSavings" => {
+                serde_json::from_value(content).map(MonetaryAccount::MonetaryAccountExternalSavings)
+            }
+            "MonetaryAccountSavings" => {
+                serde_json::from_value(content).map(MonetaryAccount::MonetaryAccountSavings)
+            }
+            _ => serde_json::from_value(content).map(|account| {
+                MonetaryAccount::Unknown(MonetaryAccountUnknown { kind, ..account })
+            }),
+        };
+
+        account.map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for MonetaryAccount {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+
+        match self {
+            MonetaryAccount::MonetaryAccountBank(account) => {
+                map.serialize_entry("MonetaryAccountBank", account)?
+            }
+            MonetaryAccount::MonetaryAccountJoint(account) => {
+                map.serialize_entry("MonetaryAccountJoint", account)?
+            }
+            MonetaryAccount::MonetaryAccountExternalSavings(account) => {
+                map.serialize_entry("MonetaryAccountExternalSavings", account)?
+            }
+            MonetaryAccount::MonetaryAccountSavings(account) => {
+                map.serialize_entry("MonetaryAccountSavings", account)?
+            }
+            MonetaryAccount::Unknown(account) => map.serialize_entry(&account.kind, account)?,
+        }
+
+        map.end()
+    }
 }
 
 impl MonetaryAccount {
@@ -56,6 +308,9 @@ impl MonetaryAccount {
             MonetaryAccount::MonetaryAccountSavings(account) => {
                 format!("{} : {}", &account.display_name, &account.description)
             }
+            MonetaryAccount::Unknown(account) => {
+                format!("{} : {}", &account.display_name, &account.description)
+            }
         }
     }
 
@@ -65,6 +320,7 @@ impl MonetaryAccount {
             MonetaryAccount::MonetaryAccountJoint(account) => &account.balance,
             MonetaryAccount::MonetaryAccountExternalSavings(account) => &account.balance,
             MonetaryAccount::MonetaryAccountSavings(account) => &account.balance,
+            MonetaryAccount::Unknown(account) => &account.balance,
         }
     }
 
@@ -74,6 +330,7 @@ impl MonetaryAccount {
             MonetaryAccount::MonetaryAccountJoint(account) => account.id,
             MonetaryAccount::MonetaryAccountExternalSavings(account) => account.id,
             MonetaryAccount::MonetaryAccountSavings(account) => account.id,
+            MonetaryAccount::Unknown(account) => account.id,
         }
     }
 
@@ -83,11 +340,50 @@ impl MonetaryAccount {
             MonetaryAccount::MonetaryAccountJoint(account) => &account.status,
             MonetaryAccount::MonetaryAccountExternalSavings(account) => &account.status,
             MonetaryAccount::MonetaryAccountSavings(account) => &account.status,
+            MonetaryAccount::Unknown(account) => &account.status,
+        }
+    }
+
+    /// The pointers (IBAN, email, etc.) through which the account can be reached
+    pub fn get_aliases(&self) -> &[Alias] {
+        match self {
+            MonetaryAccount::MonetaryAccountBank(account) => &account.alias,
+            MonetaryAccount::MonetaryAccountJoint(account) => &account.alias,
+            MonetaryAccount::MonetaryAccountExternalSavings(account) => &account.alias,
+            MonetaryAccount::MonetaryAccountSavings(account) => &account.alias,
+            MonetaryAccount::Unknown(account) => &account.alias,
+        }
+    }
+
+    /// The picture of the account, see [`crate::domains::avatar::get_avatar`] for its image
+    pub fn get_avatar(&self) -> Option<&Avatar> {
+        match self {
+            MonetaryAccount::MonetaryAccountBank(account) => account.avatar.as_ref(),
+            MonetaryAccount::MonetaryAccountJoint(account) => account.avatar.as_ref(),
+            MonetaryAccount::MonetaryAccountExternalSavings(account) => account.avatar.as_ref(),
+            MonetaryAccount::MonetaryAccountSavings(account) => account.avatar.as_ref(),
+            MonetaryAccount::Unknown(account) => account.avatar.as_ref(),
+        }
+    }
+
+    /// The IBAN of the account, if it has one
+    pub fn get_iban(&self) -> Option<&str> {
+        self.get_aliases()
+            .find_iban()
+            .map(|alias| alias.value.as_str())
+    }
+
+    /// The interest rate of the account, only known for external savings accounts
+    pub fn get_interest_rate(&self) -> Option<Decimal> {
+        match self {
+            MonetaryAccount::MonetaryAccountExternalSavings(account) => account.get_interest_rate(),
+            _ => None,
         }
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
 pub struct MonetaryAccountBank {
     pub currency: String,
     pub balance: Amount,
@@ -96,9 +392,13 @@ pub struct MonetaryAccountBank {
     pub description: String,
     pub display_name: String,
     pub id: MonetaryAccountId,
+    #[serde(default)]
+    pub alias: Vec<Alias>,
+    pub avatar: Option<Avatar>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
 pub struct MonetaryAccountJoint {
     pub currency: String,
     pub balance: Amount,
@@ -107,9 +407,13 @@ pub struct MonetaryAccountJoint {
     pub description: String,
     pub display_name: String,
     pub id: MonetaryAccountId,
+    #[serde(default)]
+    pub alias: Vec<Alias>,
+    pub avatar: Option<Avatar>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
 pub struct MonetaryAccountSavings {
     pub currency: String,
     pub balance: Amount,
@@ -118,9 +422,39 @@ pub struct MonetaryAccountSavings {
     pub description: String,
     pub display_name: String,
     pub id: MonetaryAccountId,
+    #[serde(default)]
+    pub alias: Vec<Alias>,
+    pub avatar: Option<Avatar>,
+    /// bunq sends this as a string
+    #[serde(deserialize_with = "deserialize_string_number")]
     pub number_of_payment_remaining: u8,
+    /// The amount the user wants to save up to, if they set a goal
+    #[serde(default)]
+    pub savings_goal: Option<Amount>,
+    /// How far along the goal is, from 0 to 1
+    #[serde(default)]
+    pub savings_goal_progress: Option<Decimal>,
+}
+
+impl MonetaryAccountSavings {
+    pub fn get_savings_goal(&self) -> Option<SavingsGoal> {
+        self.savings_goal.clone().map(|target| SavingsGoal {
+            target,
+            progress: self.savings_goal_progress.unwrap_or_default(),
+        })
+    }
+}
+
+/// The amount to save up to on a savings account, and how far along it is
+#[derive(Debug, Clone)]
+pub struct SavingsGoal {
+    pub target: Amount,
+    /// From 0 to 1, where 1 means the goal was reached
+    pub progress: Decimal,
 }
-#[derive(Deserialize, Debug)]
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
 pub struct MonetaryAccountExternalSavings {
     pub currency: String,
     pub balance: Amount,
@@ -129,10 +463,46 @@ pub struct MonetaryAccountExternalSavings {
     pub description: String,
     pub display_name: String,
     pub id: MonetaryAccountId,
+    #[serde(default)]
+    pub alias: Vec<Alias>,
+    pub avatar: Option<Avatar>,
+    /// bunq sends this as a string
+    #[serde(deserialize_with = "deserialize_string_number")]
     pub number_of_payment_remaining: u8,
+    /// The name of the bank that holds the savings
+    pub external_bank_name: Option<String>,
+    /// The interest rate, in percent per year
+    pub interest_rate: Option<Decimal>,
 }
 
-#[derive(Deserialize, Debug, Copy, Clone)]
+impl MonetaryAccountExternalSavings {
+    pub fn get_external_bank_name(&self) -> Option<&str> {
+        self.external_bank_name.as_deref()
+    }
+
+    pub fn get_interest_rate(&self) -> Option<Decimal> {
+        self.interest_rate
+    }
+}
+
+/// An account of a kind that's not modelled (yet), with the fields all accounts have
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MonetaryAccountUnknown {
+    /// The kind bunq wrapped the account in, e.g. `MonetaryAccountCard`
+    #[serde(skip)]
+    pub kind: String,
+    pub currency: String,
+    pub balance: Amount,
+    pub status: Status,
+    pub description: String,
+    pub display_name: String,
+    pub id: MonetaryAccountId,
+    #[serde(default)]
+    pub alias: Vec<Alias>,
+    pub avatar: Option<Avatar>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct MonetaryAccountId(pub u64);
 
 impl From<MonetaryAccountId> for String {
@@ -141,19 +511,125 @@ impl From<MonetaryAccountId> for String {
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
 pub struct Amount {
     pub currency: String,
-    pub value: String,
+    /// bunq sends this as a string such as `-12.30`, which is kept as is when serializing
+    pub value: Decimal,
 }
 
-impl Into<String> for Amount {
-    fn into(self) -> String {
+impl From<Amount> for String {
+    fn from(amount: Amount) -> String {
+        amount.value.to_string()
+    }
+}
+
+impl Display for Amount {
+    /// Formats the amount as its value followed by the currency, e.g. `12.34 EUR`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.value, self.currency)
+    }
+}
+
+impl Amount {
+    pub fn new(value: Decimal, currency: impl Into<String>) -> Self {
+        Amount {
+            currency: currency.into(),
+            value,
+        }
+    }
+
+    /// The value of the amount as a decimal, e.g. to calculate with
+    pub fn as_decimal(&self) -> Decimal {
         self.value
     }
+
+    /// Whether the amount is below zero, e.g. for money that left an account
+    pub fn is_negative(&self) -> bool {
+        self.value.is_sign_negative() && !self.value.is_zero()
+    }
+
+    /// Add up two amounts of the same currency, amounts in different currencies can't be
+    /// added up without converting one of them
+    pub fn add(&self, other: &Amount) -> Result<Amount, BunqyyError> {
+        if self.currency != other.currency {
+            return Err(BunqyyError::CurrencyMismatch(
+                self.currency.clone(),
+                other.currency.clone(),
+            ));
+        }
+
+        Ok(Amount::new(self.value + other.value, self.currency.clone()))
+    }
+
+    /// Format the amount the way it's written in a locale such as `nl-NL` or `en-US`,
+    /// e.g. `€1.234,56` or `€1,234.56`. Only the language of the locale is considered,
+    /// unknown languages are formatted like English.
+    pub fn format_localized(&self, locale: &str) -> String {
+        let language = locale
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        let (decimal_separator, thousands_separator, symbol_first) = match language.as_str() {
+            "nl" => (',', '.', true),
+            "de" | "es" | "it" | "pt" => (',', '.', false),
+            "fr" => (',', '\u{202f}', false),
+            _ => ('.', ',', true),
+        };
+
+        let formatted = self.value.to_string();
+        let (negative, value) = match formatted.strip_prefix('-') {
+            Some(value) => (true, value),
+            None => (false, formatted.as_str()),
+        };
+        let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+
+        let mut number = group_thousands(integer, thousands_separator);
+        if !fraction.is_empty() {
+            number.push(decimal_separator);
+            number.push_str(fraction);
+        }
+
+        let symbol = currency_symbol(&self.currency);
+        let sign = if negative { "-" } else { "" };
+
+        match (symbol_first, symbol) {
+            (true, Some(symbol)) => format!("{}{}{}", sign, symbol, number),
+            (true, None) => format!("{}{} {}", sign, self.currency, number),
+            (false, Some(symbol)) => format!("{}{} {}", sign, number, symbol),
+            (false, None) => format!("{}{} {}", sign, number, self.currency),
+        }
+    }
 }
 
-#[derive(Deserialize, Debug)]
+/// The symbol of the most common currencies, others are written as their code
+fn currency_symbol(currency: &str) -> Option<&'static str> {
+    match currency {
+        "EUR" => Some("€"),
+        "USD" => Some("$"),
+        "GBP" => Some("£"),
+        "JPY" => Some("¥"),
+        _ => None,
+    }
+}
+
+fn group_thousands(digits: &str, separator: char) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+
+    grouped
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Status {
     #[serde(alias = "ACTIVE")]
     Active,
@@ -164,3 +640,253 @@ pub enum Status {
     #[serde(alias = "PENDING_REOPEN")]
     PendingReopen,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{process_response_content, BunqResponse};
+
+    use std::str::FromStr;
+
+    fn amount(value: &str, currency: &str) -> Amount {
+        Amount::new(Decimal::from_str(value).unwrap(), currency)
+    }
+
+    #[test]
+    fn amounts_of_the_same_currency_are_added_up() {
+        let total = amount("12.30", "EUR").add(&amount("-2.05", "EUR")).unwrap();
+        assert_eq!(total.to_string(), "10.25 EUR");
+        assert!(!total.is_negative());
+        assert!(amount("-0.01", "EUR").is_negative());
+
+        assert!(matches!(
+            amount("1.00", "EUR").add(&amount("1.00", "USD")),
+            Err(BunqyyError::CurrencyMismatch(left, right)) if left == "EUR" && right == "USD"
+        ));
+    }
+
+    #[test]
+    fn amount_keeps_the_string_representation_of_bunq() {
+        let amount: Amount =
+            serde_json::from_str(r#"{ "currency": "EUR", "value": "-12.30" }"#).unwrap();
+
+        assert_eq!(amount.as_decimal(), Decimal::new(-1230, 2));
+        assert_eq!(String::from(amount.clone()), "-12.30");
+        assert_eq!(
+            serde_json::to_string(&amount).unwrap(),
+            r#"{"currency":"EUR","value":"-12.30"}"#
+        );
+    }
+
+    #[test]
+    fn amount_is_displayed_with_its_currency() {
+        assert_eq!(amount("12.34", "EUR").to_string(), "12.34 EUR");
+    }
+
+    #[test]
+    fn amount_is_formatted_for_a_locale() {
+        assert_eq!(amount("12.34", "EUR").format_localized("nl-NL"), "€12,34");
+        assert_eq!(
+            amount("1234.56", "EUR").format_localized("nl-NL"),
+            "€1.234,56"
+        );
+        assert_eq!(
+            amount("1234.56", "USD").format_localized("en-US"),
+            "$1,234.56"
+        );
+        assert_eq!(
+            amount("-1234.56", "EUR").format_localized("de_DE"),
+            "-1.234,56 €"
+        );
+        assert_eq!(amount("100.00", "CHF").format_localized("en"), "CHF 100.00");
+        assert_eq!(
+            amount("1000000.00", "GBP").format_localized("en-GB"),
+            "£1,000,000.00"
+        );
+    }
+
+    fn account_json(kind: &str, id: u64, status: &str) -> String {
+        let savings_fields = match kind {
+            "MonetaryAccountSavings" | "MonetaryAccountExternalSavings" => {
+                r#", "number_of_payment_remaining": "3""#
+            }
+            _ => "",
+        };
+
+        format!(
+            r#"{{
+                "{}": {{
+                    "id": {},
+                    "currency": "EUR",
+                    "balance": {{ "currency": "EUR", "value": "10.00" }},
+                    "status": "{}",
+                    "sub_status": "NONE",
+                    "description": "Account {}",
+                    "display_name": "bunqyy"{}
+                }}
+            }}"#,
+            kind, id, status, id, savings_fields
+        )
+    }
+
+    #[test]
+    fn new_kind_of_account_is_unknown() {
+        let response = format!(
+            r#"{{ "Response": [{}, {}] }}"#,
+            account_json("MonetaryAccountBank", 1, "ACTIVE"),
+            account_json("MonetaryAccountCard", 2, "ACTIVE")
+                .replace(r#""id": 2,"#, r#""id": 2, "card_id": 5,"#),
+        );
+
+        let accounts = match process_response_content::<MonetaryAccount>(&response).unwrap() {
+            BunqResponse::Success(content) => content.response,
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        let MonetaryAccount::Unknown(unknown) = &accounts[1] else {
+            panic!("Expected an unknown account");
+        };
+        assert_eq!(unknown.kind, "MonetaryAccountCard");
+        assert_eq!(accounts[1].get_id(), MonetaryAccountId(2));
+        assert_eq!(accounts[1].get_name(), "bunqyy : Account 2");
+
+        let json = serde_json::to_value(&accounts[1]).unwrap();
+        assert_eq!(json["MonetaryAccountCard"]["id"], 2);
+    }
+
+    #[test]
+    fn account_that_cannot_be_read_does_not_drop_the_others() {
+        let response = format!(
+            r#"{{ "Response": [{}, {}, {}] }}"#,
+            account_json("MonetaryAccountBank", 1, "ACTIVE"),
+            r#"{ "MonetaryAccountLight": { "id": 2 } }"#,
+            account_json("MonetaryAccountInvestment", 3, "ACTIVE"),
+        );
+
+        let entries = match process_response_content::<Value>(&response).unwrap() {
+            BunqResponse::Success(content) => content.response,
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        let accounts = deserialize_entries::<MonetaryAccount>(entries);
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].get_id(), MonetaryAccountId(1));
+        assert_eq!(accounts[1].get_id(), MonetaryAccountId(3));
+        assert!(matches!(accounts[1], MonetaryAccount::Unknown(_)));
+    }
+
+    #[test]
+    fn external_savings_interest_rate_is_exposed() {
+        let response = format!(
+            r#"{{ "Response": [{}, {}] }}"#,
+            account_json("MonetaryAccountBank", 1, "ACTIVE"),
+            account_json("MonetaryAccountExternalSavings", 2, "ACTIVE").replace(
+                r#""id": 2,"#,
+                r#""id": 2, "external_bank_name": "Other bank", "interest_rate": "1.75","#
+            ),
+        );
+
+        let accounts = match process_response_content::<MonetaryAccount>(&response).unwrap() {
+            BunqResponse::Success(content) => content.response,
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        assert_eq!(accounts[0].get_interest_rate(), None);
+        assert_eq!(accounts[1].get_interest_rate(), Some(Decimal::new(175, 2)));
+    }
+
+    #[test]
+    fn savings_goal_is_exposed() {
+        let account: MonetaryAccount = serde_json::from_str(
+            &account_json("MonetaryAccountSavings", 3, "ACTIVE").replace(
+                r#""id": 3,"#,
+                r#""id": 3, "savings_goal": { "currency": "EUR", "value": "500.00" }, "savings_goal_progress": 0.25,"#,
+            ),
+        )
+        .unwrap();
+
+        let MonetaryAccount::MonetaryAccountSavings(savings) = account else {
+            panic!("Expected a savings account");
+        };
+        let goal = savings.get_savings_goal().unwrap();
+        assert_eq!(goal.target.value.to_string(), "500.00");
+        assert_eq!(goal.progress, Decimal::new(25, 2));
+
+        let MonetaryAccount::MonetaryAccountSavings(without_goal) =
+            serde_json::from_str(&account_json("MonetaryAccountSavings", 4, "ACTIVE")).unwrap()
+        else {
+            panic!("Expected a savings account");
+        };
+        assert!(without_goal.get_savings_goal().is_none());
+    }
+
+    #[test]
+    fn monetary_accounts_round_trip_through_serialization() {
+        let account: MonetaryAccount =
+            serde_json::from_str(&account_json("MonetaryAccountSavings", 3, "ACTIVE")).unwrap();
+
+        let serialized = serde_json::to_string(&account).unwrap();
+        let deserialized: MonetaryAccount = serde_json::from_str(&serialized).unwrap();
+
+        assert!(matches!(
+            deserialized,
+            MonetaryAccount::MonetaryAccountSavings(_)
+        ));
+        assert_eq!(deserialized.get_id().0, 3);
+        assert_eq!(deserialized.get_status(), &Status::Active);
+        assert_eq!(
+            deserialized.get_balance().value.to_string(),
+            account.get_balance().value.to_string()
+        );
+    }
+
+    #[test]
+    fn own_ibans_are_taken_from_account_aliases() {
+        let response = format!(
+            r#"{{ "Response": [{}, {}] }}"#,
+            account_json("MonetaryAccountBank", 1, "ACTIVE").replace(
+                r#""id": 1,"#,
+                r#""id": 1, "alias": [
+                    {"type": "EMAIL", "value": "me@example.com", "name": "Me"},
+                    {"type": "IBAN", "value": "NL00BUNQ0000000001", "name": "Me"}
+                ],"#
+            ),
+            account_json("MonetaryAccountSavings", 2, "ACTIVE"),
+        );
+
+        let accounts = match process_response_content::<MonetaryAccount>(&response).unwrap() {
+            BunqResponse::Success(content) => content.response,
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        let ibans = own_ibans(&accounts);
+
+        assert_eq!(ibans.len(), 1);
+        assert_eq!(ibans[0].monetary_account_id.0, 1);
+        assert_eq!(ibans[0].iban, "NL00BUNQ0000000001");
+    }
+
+    #[test]
+    fn only_active_accounts_are_kept() {
+        let response = format!(
+            r#"{{ "Response": [{}, {}, {}, {}] }}"#,
+            account_json("MonetaryAccountBank", 1, "ACTIVE"),
+            account_json("MonetaryAccountBank", 2, "CANCELLED"),
+            account_json("MonetaryAccountSavings", 3, "ACTIVE"),
+            account_json("MonetaryAccountJoint", 4, "BLOCKED"),
+        );
+
+        let accounts = match process_response_content::<MonetaryAccount>(&response).unwrap() {
+            BunqResponse::Success(content) => content.response,
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        let active_ids: Vec<u64> = only_active(accounts)
+            .iter()
+            .map(|account| account.get_id().0)
+            .collect();
+
+        assert_eq!(active_ids, vec![1, 3]);
+    }
+}
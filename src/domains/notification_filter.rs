@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+use crate::api_context::ManagedApiContext;
+use crate::common::BunqyyError;
+use crate::http::{get_authenticated_client, send_request};
+
+/// Get the categories of events the user receives push notifications on their phone for
+pub async fn get_push_notification_filters(
+    api_context: &ManagedApiContext,
+) -> anyhow::Result<Vec<NotificationCategory>> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!("{}/user/{}/notification-filter-push", base_url, user_id);
+
+    Ok(
+        send_request::<NotificationFilterPushWrapper>(client.get(url))
+            .await?
+            .into_iter()
+            .map(|entry| entry.notification_filter_push.category)
+            .collect(),
+    )
+}
+
+/// Set the categories of events the user receives push notifications for, replacing the
+/// ones that were set before. An empty list turns push notifications off.
+/// [`NotificationCategory::Other`] is refused, as bunq wouldn't know which category is meant.
+pub async fn set_push_notification_filters(
+    api_context: &ManagedApiContext,
+    categories: &[NotificationCategory],
+) -> anyhow::Result<()> {
+    let body = push_notification_filters_body(categories)?;
+
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!("{}/user/{}/notification-filter-push", base_url, user_id);
+
+    send_request::<serde_json::Value>(client.post(url).body(body)).await?;
+
+    Ok(())
+}
+
+fn push_notification_filters_body(
+    categories: &[NotificationCategory],
+) -> Result<String, BunqyyError> {
+    if categories.contains(&NotificationCategory::Other) {
+        return Err(BunqyyError::UnknownNotificationCategory);
+    }
+
+    #[derive(Serialize, Debug)]
+    struct Payload {
+        notification_filters: Vec<NotificationFilterPush>,
+    }
+
+    let payload = Payload {
+        notification_filters: categories
+            .iter()
+            .map(|category| NotificationFilterPush {
+                category: *category,
+            })
+            .collect(),
+    };
+
+    Ok(serde_json::to_string(&payload)?)
+}
+
+#[derive(Deserialize, Debug)]
+struct NotificationFilterPushWrapper {
+    #[serde(rename = "NotificationFilterPush")]
+    notification_filter_push: NotificationFilterPush,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+struct NotificationFilterPush {
+    category: NotificationCategory,
+}
+
+/// The kinds of events bunq notifies about
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NotificationCategory {
+    Billing,
+    BunqmeTab,
+    CardTransactionFailed,
+    CardTransactionSuccessful,
+    Chat,
+    DraftPayment,
+    Ideal,
+    Mutation,
+    Oauth,
+    Payment,
+    Request,
+    ScheduleResult,
+    ScheduleStatus,
+    Share,
+    Sofort,
+    Support,
+    TabResult,
+    /// A category that's not modelled (yet), which can't be set
+    #[serde(other)]
+    Other,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{process_response_content, BunqResponse};
+
+    #[test]
+    fn push_notification_filters_are_deserialized() {
+        let response = r#"
+        {
+            "Response": [
+                { "NotificationFilterPush": { "category": "PAYMENT" } },
+                { "NotificationFilterPush": { "category": "CARD_TRANSACTION_SUCCESSFUL" } },
+                { "NotificationFilterPush": { "category": "SOMETHING_NEW" } }
+            ]
+        }
+        "#;
+
+        let categories: Vec<NotificationCategory> =
+            match process_response_content::<NotificationFilterPushWrapper>(response).unwrap() {
+                BunqResponse::Success(content) => content
+                    .response
+                    .into_iter()
+                    .map(|entry| entry.notification_filter_push.category)
+                    .collect(),
+                BunqResponse::Error(_) => panic!("Expected success"),
+            };
+
+        assert_eq!(
+            categories,
+            vec![
+                NotificationCategory::Payment,
+                NotificationCategory::CardTransactionSuccessful,
+                NotificationCategory::Other,
+            ]
+        );
+    }
+
+    #[test]
+    fn only_known_categories_can_be_set() {
+        assert_eq!(
+            push_notification_filters_body(&[NotificationCategory::Payment]).unwrap(),
+            r#"{"notification_filters":[{"category":"PAYMENT"}]}"#
+        );
+
+        assert!(matches!(
+            push_notification_filters_body(&[
+                NotificationCategory::Payment,
+                NotificationCategory::Other
+            ]),
+            Err(BunqyyError::UnknownNotificationCategory)
+        ));
+    }
+}
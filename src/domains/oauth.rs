@@ -1,61 +1,283 @@
 use std::io::{stdin, stdout, Write};
-use std::process::exit;
 
 use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
 use url::Url;
 
+use crate::api_context::{
+    setup_api_context_with_access_token, ApiContext, ContextStore, Environment,
+};
 use crate::common::{BunqyyError, SetupContext};
+use crate::signing::{base64_url, random_bytes, sha256};
 
 const BUNQ_OAUTH_BASE_URL: &str = "https://api.oauth.bunq.com/v1";
 const BUNQ_TOKEN_ENDPOINT: &str = constcat::concat!(BUNQ_OAUTH_BASE_URL, "/token");
 const BUNQ_OAUTH_GRANT_PAGE_URL: &str = "https://oauth.bunq.com/auth";
 
+const BUNQ_SANDBOX_OAUTH_BASE_URL: &str = "https://api-oauth.sandbox.bunq.com/v1";
+const BUNQ_SANDBOX_TOKEN_ENDPOINT: &str = constcat::concat!(BUNQ_SANDBOX_OAUTH_BASE_URL, "/token");
+const BUNQ_SANDBOX_OAUTH_GRANT_PAGE_URL: &str = "https://oauth.sandbox.bunq.com/auth";
+
 const REDIRECT_URI: &str = "http://127.0.0.1:5454";
+/// Where the redirect uri is listened on when capturing the redirect
+const REDIRECT_ADDRESS: &str = "127.0.0.1:5454";
+/// The most of a redirect request that's read, the code is in its first line
+const MAX_REDIRECT_REQUEST_SIZE: usize = 8192;
+
+const REDIRECT_CAPTURED_PAGE: &str = "<!DOCTYPE html><html><body>\
+<h1>bunqyy is connected to your bunq account</h1>\
+<p>You can close this tab.</p>\
+</body></html>";
+const REDIRECT_FAILED_PAGE: &str = "<!DOCTYPE html><html><body>\
+<h1>bunqyy did not get access to your bunq account</h1>\
+<p>You can close this tab and try again.</p>\
+</body></html>";
 
-/// Get the access token by performing the oauth flow
+/// Get the access token by performing the oauth flow interactively, through stdin and stdout.
+/// With [`SetupContext::with_redirect_capture`] the code is taken from the redirect instead.
+/// Either way the state the flow was started with is checked before the code is exchanged.
+/// To drive the flow some other way, send the user to [`auth_url`] and pass the code bunq
+/// redirects back with to [`complete_oauth`].
 pub async fn get_access_token(setup_context: &SetupContext) -> Result<String, BunqyyError> {
-    let url = create_auth_url(&setup_context);
+    let (url, state) = auth_url_with_state(setup_context);
+
+    if setup_context.capture_redirect {
+        match TcpListener::bind(REDIRECT_ADDRESS).await {
+            Ok(listener) => {
+                println!("Visit the URL below and follow the process");
+                println!("{}", url);
+                stdout().flush().expect("cannot flush");
+
+                match capture_redirect(&listener).await {
+                    Ok(redirect) => {
+                        info!("Captured the code of the oauth redirect");
+                        let redirect = redirect?;
+                        state.verify(redirect.state.as_deref())?;
+
+                        return exchange_token(redirect.code.as_str(), setup_context).await;
+                    }
+                    Err(e) => warn!("Failed to capture the oauth redirect: {}", e),
+                }
+            }
+            Err(e) => warn!(
+                "Cannot listen for the oauth redirect on {}: {}",
+                REDIRECT_ADDRESS, e
+            ),
+        }
+    }
 
     println!("Visit the URL below and follow the process");
-    println!("{}", url.to_string());
-    println!("Find the \"code\" in your redirect URL and paste it here:");
+    println!("{}", url);
+    println!("Paste the URL you were redirected to here:");
     stdout().flush().expect("cannot flush");
-    let mut code = String::new();
+    let mut redirect_url = String::new();
 
-    stdin().read_line(&mut code).expect("Did not enter a code");
+    stdin()
+        .read_line(&mut redirect_url)
+        .expect("Did not enter a URL");
 
-    code = code.trim().to_string();
+    let redirect = Url::parse(redirect_url.trim())
+        .ok()
+        .and_then(|url| parse_redirect_url(&url))
+        .ok_or(BunqyyError::InvalidAuthorizationCode(
+            redirect_url.trim().to_string(),
+        ))??;
+    state.verify(redirect.state.as_deref())?;
 
-    if code.len() < 4 {
-        println!("You probably didn't enter a correct code");
-        exit(1)
+    exchange_token(redirect.code.as_str(), setup_context).await
+}
+
+/// What bunq redirected back with after the user granted access
+#[derive(Debug)]
+struct Redirect {
+    code: String,
+    state: Option<String>,
+}
+
+/// Wait for the browser to follow the redirect after the user granted (or denied) access, and
+/// take the code from it. Other requests, such as one for a favicon, are turned away.
+async fn capture_redirect(
+    listener: &TcpListener,
+) -> std::io::Result<Result<Redirect, BunqyyError>> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+
+        let mut buffer = vec![0; MAX_REDIRECT_REQUEST_SIZE];
+        let length = stream.read(&mut buffer).await?;
+        let request = String::from_utf8_lossy(&buffer[..length]);
+
+        let Some(redirect) = parse_redirect(&request) else {
+            stream
+                .write_all(http_response("404 Not Found", "").as_bytes())
+                .await?;
+            continue;
+        };
+
+        let page = match &redirect {
+            Ok(_) => REDIRECT_CAPTURED_PAGE,
+            Err(_) => REDIRECT_FAILED_PAGE,
+        };
+        stream
+            .write_all(http_response("200 OK", page).as_bytes())
+            .await?;
+        stream.shutdown().await?;
+
+        return Ok(redirect);
     }
+}
+
+/// Take the code from the request the browser makes when it's redirected back, or the error
+/// bunq redirected with when access was denied. `None` for requests that aren't a redirect.
+fn parse_redirect(request: &str) -> Option<Result<Redirect, BunqyyError>> {
+    let target = request
+        .lines()
+        .next()?
+        .strip_prefix("GET ")?
+        .split(' ')
+        .next()?;
 
-    exchange_token(code.as_str(), &setup_context).await
+    parse_redirect_url(&Url::parse(REDIRECT_URI).ok()?.join(target).ok()?)
 }
 
-/// Exchange the code bunqyy gave back for a real access token
-async fn exchange_token(code: &str, setup_context: &SetupContext) -> Result<String, BunqyyError> {
+/// Take the code and state from the url bunq redirected to, see [`parse_redirect`]
+fn parse_redirect_url(url: &Url) -> Option<Result<Redirect, BunqyyError>> {
+    let parameter = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    if let Some(error) = parameter("error") {
+        return Some(Err(BunqyyError::OAuth {
+            error,
+            description: parameter("error_description"),
+        }));
+    }
+
+    parameter("code").map(|code| {
+        Ok(Redirect {
+            code,
+            state: parameter("state"),
+        })
+    })
+}
+
+fn http_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Finish the oauth flow with the code bunq redirected back with, after the user granted
+/// access at the [`auth_url`]. The api context is set up and persisted at the storage path of
+/// the setup context, so [`crate::api_context::get_api_context_at_storage_path`] picks it up
+/// from then on.
+pub async fn complete_oauth(
+    setup_context: &SetupContext,
+    code: &str,
+) -> anyhow::Result<ApiContext> {
+    let api_key = exchange_token(code, setup_context).await?;
+
+    let store = setup_context.file_store();
+    let api_context =
+        setup_api_context_with_access_token(setup_context, &store, api_key, &|_| {}).await?;
+    store.save(&api_context).await?;
+
+    Ok(api_context)
+}
+
+/// Finish the oauth flow like [`complete_oauth`], for a flow that was started at the
+/// [`auth_url_with_pkce`] with this verifier
+pub async fn complete_oauth_with_pkce(
+    setup_context: &SetupContext,
+    code: &str,
+    code_verifier: &PkceVerifier,
+) -> anyhow::Result<ApiContext> {
+    let api_key = exchange_token_with_pkce(code, setup_context, code_verifier).await?;
+
+    let store = setup_context.file_store();
+    let api_context =
+        setup_api_context_with_access_token(setup_context, &store, api_key, &|_| {}).await?;
+    store.save(&api_context).await?;
+
+    Ok(api_context)
+}
+
+/// Exchange the code bunq gave back for a real access token
+pub async fn exchange_token(
+    code: &str,
+    setup_context: &SetupContext,
+) -> Result<String, BunqyyError> {
+    request_token(code, setup_context, None).await
+}
+
+/// Exchange the code bunq gave back for a real access token, proving the flow was started
+/// with the verifier's challenge. The client secret may be left empty for public clients.
+pub async fn exchange_token_with_pkce(
+    code: &str,
+    setup_context: &SetupContext,
+    code_verifier: &PkceVerifier,
+) -> Result<String, BunqyyError> {
+    request_token(code, setup_context, Some(code_verifier)).await
+}
+
+async fn request_token(
+    code: &str,
+    setup_context: &SetupContext,
+    code_verifier: Option<&PkceVerifier>,
+) -> Result<String, BunqyyError> {
+    let code = code.trim();
+
+    if code.len() < 4 {
+        return Err(BunqyyError::InvalidAuthorizationCode(code.to_string()));
+    }
+
+    let mut query = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("client_id", setup_context.client_id.as_str()),
+        ("redirect_uri", REDIRECT_URI),
+    ];
+
+    if !setup_context.client_secret.is_empty() {
+        query.push(("client_secret", setup_context.client_secret.as_str()));
+    }
+
+    if let Some(code_verifier) = code_verifier {
+        query.push(("code_verifier", code_verifier.as_str()));
+    }
+
     let client = reqwest::Client::new();
 
     let response = client
-        .post(BUNQ_TOKEN_ENDPOINT)
-        .query(&[
-            ("grant_type", "authorization_code"),
-            ("code", code),
-            ("client_id", setup_context.client_id.as_str()),
-            ("client_secret", setup_context.client_secret.as_str()),
-            ("redirect_uri", REDIRECT_URI),
-        ])
+        .post(token_endpoint(setup_context))
+        .query(&query)
         .send()
         .await?;
 
-    Ok(response.json::<TokenExchangeResult>().await?.access_token)
+    parse_token_response(&response.text().await?)
 }
 
-/// Create an url that should be followed to execute the oauth grant at bunqyy's website
-fn create_auth_url(setup_context: &SetupContext) -> Url {
-    let mut url = Url::parse(BUNQ_OAUTH_GRANT_PAGE_URL).expect("URL to be created");
+/// Take the access token from the response of the token endpoint, or the error it reported
+fn parse_token_response(body: &str) -> Result<String, BunqyyError> {
+    match serde_json::from_str::<TokenExchangeResponse>(body)? {
+        TokenExchangeResponse::Success(result) => Ok(result.access_token),
+        TokenExchangeResponse::Error(error) => Err(BunqyyError::OAuth {
+            error: error.error,
+            description: error.error_description,
+        }),
+    }
+}
+
+/// The url the user should visit to grant access at bunq's website, after which bunq
+/// redirects back with the code to pass to [`complete_oauth`]
+pub fn auth_url(setup_context: &SetupContext) -> Url {
+    let mut url = Url::parse(&grant_page_url(setup_context)).expect("URL to be created");
 
     url.query_pairs_mut()
         .append_pair("response_type", "code")
@@ -65,9 +287,282 @@ fn create_auth_url(setup_context: &SetupContext) -> Url {
     url
 }
 
+/// The url the user should visit to grant access, like [`auth_url`], with a random state that
+/// bunq redirects back with. Keep the state until then and [verify](OAuthState::verify) the
+/// redirect carries it, so a code of someone else's flow can't be slipped in.
+pub fn auth_url_with_state(setup_context: &SetupContext) -> (Url, OAuthState) {
+    let state = OAuthState::new();
+    let mut url = auth_url(setup_context);
+
+    url.query_pairs_mut().append_pair("state", state.as_str());
+
+    (url, state)
+}
+
+/// The state an oauth flow is started with, which bunq includes in its redirect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OAuthState(String);
+
+impl OAuthState {
+    /// Generate a new random state
+    pub fn new() -> Self {
+        OAuthState(base64_url(&random_bytes::<16>()))
+    }
+
+    /// Check the state the redirect came back with is this one
+    pub fn verify(&self, returned_state: Option<&str>) -> Result<(), BunqyyError> {
+        match returned_state {
+            Some(returned_state) if returned_state == self.0 => Ok(()),
+            _ => Err(BunqyyError::OAuthStateMismatch),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Default for OAuthState {
+    fn default() -> Self {
+        OAuthState::new()
+    }
+}
+
+impl From<String> for OAuthState {
+    fn from(state: String) -> Self {
+        OAuthState(state)
+    }
+}
+
+/// The url the user should visit to grant access, like [`auth_url`], protected with PKCE.
+/// Keep the verifier until bunq redirects back, e.g. in the session of a web backend, and
+/// pass it to [`complete_oauth_with_pkce`] along with the code.
+pub fn auth_url_with_pkce(setup_context: &SetupContext) -> (Url, PkceVerifier) {
+    let code_verifier = PkceVerifier::new();
+    let mut url = auth_url(setup_context);
+
+    url.query_pairs_mut()
+        .append_pair("code_challenge", &code_verifier.code_challenge())
+        .append_pair("code_challenge_method", "S256");
+
+    (url, code_verifier)
+}
+
+/// The secret of a PKCE protected oauth flow, of which only the challenge (its hash) is sent
+/// along when the flow starts. It's plain text so it can be persisted in between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PkceVerifier(String);
+
+impl PkceVerifier {
+    /// Generate a new random verifier
+    pub fn new() -> Self {
+        PkceVerifier(base64_url(&random_bytes::<32>()))
+    }
+
+    /// The challenge derived from the verifier with the `S256` method
+    pub fn code_challenge(&self) -> String {
+        base64_url(&sha256(self.0.as_bytes()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Default for PkceVerifier {
+    fn default() -> Self {
+        PkceVerifier::new()
+    }
+}
+
+impl From<String> for PkceVerifier {
+    fn from(code_verifier: String) -> Self {
+        PkceVerifier(code_verifier)
+    }
+}
+
+/// The endpoint that exchanges a code for an access token, which differs per environment
+fn token_endpoint(setup_context: &SetupContext) -> String {
+    if let Some(oauth_base_url) = &setup_context.oauth_base_url {
+        return format!("{}/token", oauth_base_url);
+    }
+
+    match setup_context.environment {
+        Environment::SANDBOX => BUNQ_SANDBOX_TOKEN_ENDPOINT,
+        Environment::PRODUCTION => BUNQ_TOKEN_ENDPOINT,
+    }
+    .to_string()
+}
+
+/// The page on which the user grants access, which differs per environment
+fn grant_page_url(setup_context: &SetupContext) -> String {
+    if let Some(oauth_base_url) = &setup_context.oauth_base_url {
+        return format!("{}/auth", oauth_base_url);
+    }
+
+    match setup_context.environment {
+        Environment::SANDBOX => BUNQ_SANDBOX_OAUTH_GRANT_PAGE_URL,
+        Environment::PRODUCTION => BUNQ_OAUTH_GRANT_PAGE_URL,
+    }
+    .to_string()
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum TokenExchangeResponse {
+    Success(TokenExchangeResult),
+    Error(OAuthError),
+}
+
 #[derive(Deserialize, Debug)]
 #[allow(dead_code)]
 struct TokenExchangeResult {
     access_token: String,
     token_type: String,
 }
+
+/// The error body of the token endpoint, as described by the OAuth 2.0 spec
+#[derive(Deserialize, Debug)]
+struct OAuthError {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_context() -> SetupContext {
+        SetupContext::new(
+            Environment::SANDBOX,
+            "client-id".to_string(),
+            "client-secret".to_string(),
+            ".context.json".to_string(),
+        )
+    }
+
+    #[test]
+    fn auth_url_contains_the_client() {
+        let url = auth_url(&setup_context());
+
+        assert!(url.as_str().starts_with(BUNQ_SANDBOX_OAUTH_GRANT_PAGE_URL));
+        assert!(url
+            .query_pairs()
+            .any(|(key, value)| key == "client_id" && value == "client-id"));
+    }
+
+    #[test]
+    fn token_response_is_parsed() {
+        assert_eq!(
+            parse_token_response(r#"{"access_token": "token", "token_type": "bearer"}"#).unwrap(),
+            "token"
+        );
+
+        assert!(matches!(
+            parse_token_response(r#"{"error": "invalid_grant", "error_description": "The code has expired"}"#),
+            Err(BunqyyError::OAuth { error, description })
+                if error == "invalid_grant" && description.as_deref() == Some("The code has expired")
+        ));
+
+        assert!(matches!(
+            parse_token_response("<html></html>"),
+            Err(BunqyyError::ResponseDeserialization(_))
+        ));
+    }
+
+    #[test]
+    fn pkce_challenge_is_derived_from_the_verifier() {
+        // The example of RFC 7636, appendix B
+        let code_verifier =
+            PkceVerifier::from("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string());
+        assert_eq!(
+            code_verifier.code_challenge(),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+
+        let (url, code_verifier) = auth_url_with_pkce(&setup_context());
+        assert_eq!(code_verifier.as_str().len(), 43);
+        assert!(
+            url.query_pairs()
+                .any(|(key, value)| key == "code_challenge"
+                    && value == code_verifier.code_challenge())
+        );
+        assert!(url
+            .query_pairs()
+            .any(|(key, value)| key == "code_challenge_method" && value == "S256"));
+    }
+
+    #[test]
+    fn code_is_taken_from_the_redirect() {
+        let request = "GET /?code=abcd1234&state=x HTTP/1.1\r\nHost: 127.0.0.1:5454\r\n\r\n";
+        let redirect = parse_redirect(request).unwrap().unwrap();
+        assert_eq!(redirect.code, "abcd1234");
+        assert_eq!(redirect.state.as_deref(), Some("x"));
+
+        let denied = "GET /?error=access_denied HTTP/1.1\r\n\r\n";
+        assert!(matches!(
+            parse_redirect(denied),
+            Some(Err(BunqyyError::OAuth { error, description: None })) if error == "access_denied"
+        ));
+
+        assert!(parse_redirect("GET /favicon.ico HTTP/1.1\r\n\r\n").is_none());
+        assert!(parse_redirect("").is_none());
+    }
+
+    #[tokio::test]
+    async fn redirect_is_captured_from_the_browser() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let browser = tokio::spawn(async move {
+            let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+            stream
+                .write_all(b"GET /?code=abcd1234 HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        });
+
+        let redirect = capture_redirect(&listener).await.unwrap().unwrap();
+
+        assert_eq!(redirect.code, "abcd1234");
+        assert!(browser.await.unwrap().contains("You can close this tab"));
+    }
+
+    #[test]
+    fn redirect_must_carry_the_state_the_flow_started_with() {
+        let (url, state) = auth_url_with_state(&setup_context());
+        assert!(url
+            .query_pairs()
+            .any(|(key, value)| key == "state" && value == state.as_str()));
+        assert_ne!(state, OAuthState::new());
+
+        let redirect_url = Url::parse(&format!(
+            "{}/?code=abcd1234&state={}",
+            REDIRECT_URI,
+            state.as_str()
+        ))
+        .unwrap();
+        let redirect = parse_redirect_url(&redirect_url).unwrap().unwrap();
+        assert!(state.verify(redirect.state.as_deref()).is_ok());
+
+        assert!(matches!(
+            state.verify(Some("forged")),
+            Err(BunqyyError::OAuthStateMismatch)
+        ));
+        assert!(matches!(
+            state.verify(None),
+            Err(BunqyyError::OAuthStateMismatch)
+        ));
+    }
+
+    #[tokio::test]
+    async fn short_codes_are_rejected() {
+        assert!(matches!(
+            exchange_token(" ab \n", &setup_context()).await,
+            Err(BunqyyError::InvalidAuthorizationCode(code)) if code == "ab"
+        ));
+    }
+}
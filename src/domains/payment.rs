@@ -1,65 +1,1262 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
 use anyhow::anyhow;
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::warn;
 
 use crate::api_context::ManagedApiContext;
-use crate::common::BUNQ_BASE_URL;
-use crate::domains::monetary_account::{Amount, MonetaryAccountId};
-use crate::http::{get_authenticated_client, process_response_content, BunqResponse};
+use crate::bunq_timestamp;
+use crate::common::{
+    list_url, validate_count, Alias, AliasType, BunqyyError, IdempotencyKey, ListParams,
+};
+use crate::domains::monetary_account::{get_monetary_accounts, Amount, MonetaryAccountId};
+use crate::domains::transaction::Transaction;
+use crate::http::{
+    deserialize_entries, get_authenticated_client, pagination_url, send_paginated_request,
+    send_request, send_request_allowing_errors, send_request_for_bytes, BunqError,
+    IdempotentRequestBuilder,
+};
+
+/// How many accounts' payments are fetched at the same time, bunq allows
+/// only a few requests per second before it starts rejecting them
+const MAX_CONCURRENT_REQUESTS: usize = 3;
 
+/// Get the most recent payments of an account, or another page of them with the list params.
+/// bunq returns them newest first, see [`get_payments_chronological`] for the reverse.
 pub async fn get_payments(
     api_context: &ManagedApiContext,
     monetary_account_id: MonetaryAccountId,
+    list_params: Option<ListParams>,
 ) -> anyhow::Result<Vec<Payment>> {
-    let user_id = api_context.lock().await.session_context.user_id;
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = list_url(
+        &format!(
+            "{}/user/{}/monetary-account/{}/payment",
+            base_url, user_id, monetary_account_id.0
+        ),
+        list_params,
+    )?;
+
+    let entries = send_request::<Value>(client.get(url)).await?;
+
+    Ok(deserialize_entries::<PaymentWrapper>(entries)
+        .into_iter()
+        .map(|entry| entry.payment)
+        .collect())
+}
+
+/// Get the given number of most recent payments of an account, newest first.
+/// bunq accepts a count of 1 up to and including 200, other counts are rejected up front.
+pub async fn get_payments_with_count(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    count: u32,
+) -> anyhow::Result<Vec<Payment>> {
+    get_payments(
+        api_context,
+        monetary_account_id,
+        Some(ListParams::with_count(count)),
+    )
+    .await
+}
+
+/// Get every payment of an account, newest first, by following bunq's pages until the oldest
+/// payment. For accounts with a long history, see [`payment_pages`] to handle a page at a
+/// time instead of keeping all of them in memory.
+pub async fn get_all_payments(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<Vec<Payment>> {
+    let mut pages = payment_pages(api_context, monetary_account_id).await?;
+    let mut payments = vec![];
+
+    while let Some(page) = pages.next_page().await? {
+        payments.extend(page);
+    }
+
+    Ok(payments)
+}
+
+/// Walk through the payments of an account a page at a time, newest first
+/// ```no_run
+/// # use bunqyy::api_context::ManagedApiContext;
+/// # use bunqyy::domains::monetary_account::MonetaryAccountId;
+/// # #[cfg(feature = "payments")]
+/// # async fn example(
+/// #     api_context: ManagedApiContext,
+/// #     monetary_account_id: MonetaryAccountId,
+/// #     store: impl Fn(Vec<bunqyy::domains::payment::Payment>),
+/// # ) -> anyhow::Result<()> {
+/// # use bunqyy::domains::payment::payment_pages;
+/// let mut pages = payment_pages(&api_context, monetary_account_id).await?;
+/// while let Some(page) = pages.next_page().await? {
+///     store(page);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn payment_pages(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<PaymentPages> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let url = list_url(
+        &format!(
+            "{}/user/{}/monetary-account/{}/payment",
+            base_url, user_id, monetary_account_id.0
+        ),
+        None,
+    )?;
+
+    Ok(PaymentPages {
+        api_context: api_context.clone(),
+        base_url,
+        next_url: Some(url.to_string()),
+    })
+}
+
+/// The pages of payments of an account, see [`payment_pages`]
+pub struct PaymentPages {
+    api_context: ManagedApiContext,
+    base_url: String,
+    next_url: Option<String>,
+}
+
+impl PaymentPages {
+    /// Fetch the next, older, page of payments. `None` once all pages were fetched.
+    pub async fn next_page(&mut self) -> anyhow::Result<Option<Vec<Payment>>> {
+        let Some(url) = self.next_url.take() else {
+            return Ok(None);
+        };
+
+        let client = get_authenticated_client(&self.api_context).await?;
+        let (page, pagination) = send_paginated_request::<Value>(client.get(&url)).await?;
+
+        if page.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(older_url) = pagination.and_then(|pagination| pagination.older_url) {
+            self.next_url = Some(pagination_url(&self.base_url, &older_url)?);
+        }
+
+        Ok(Some(
+            deserialize_entries::<PaymentWrapper>(page)
+                .into_iter()
+                .map(|entry| entry.payment)
+                .collect(),
+        ))
+    }
+}
+
+/// Get the payments of an account that were made after the payment with the given id,
+/// oldest first. Storing the id of the last payment that was seen and passing it the next
+/// time only fetches what's new, which makes for an efficient incremental sync.
+pub async fn get_payments_since(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    newer_than_id: PaymentId,
+) -> anyhow::Result<Vec<Payment>> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let list_params = ListParams {
+        newer_id: Some(newer_than_id.0),
+        ..Default::default()
+    };
+
+    let mut url = list_url(
+        &format!(
+            "{}/user/{}/monetary-account/{}/payment",
+            base_url, user_id, monetary_account_id.0
+        ),
+        Some(list_params),
+    )?
+    .to_string();
+
+    let mut payments = vec![];
+
+    // Each page holds at most 200 payments, the ones after it are behind its newer url
+    loop {
+        let (page, pagination) = send_paginated_request::<Value>(client.get(&url)).await?;
+        let is_empty = page.is_empty();
+
+        payments.extend(
+            deserialize_entries::<PaymentWrapper>(page)
+                .into_iter()
+                .map(|entry| entry.payment),
+        );
+
+        match pagination.and_then(|pagination| pagination.newer_url) {
+            Some(newer_url) if !is_empty => url = pagination_url(&base_url, &newer_url)?,
+            _ => break,
+        }
+    }
+
+    payments.sort_by_key(|payment| payment.id.0);
+
+    Ok(payments)
+}
+
+/// Get the most recent payments of every account of the user, e.g. for a dashboard.
+/// The payments of the accounts are fetched concurrently, but only a few at a time
+/// to stay within bunq's rate limits.
+pub async fn get_payments_for_all_accounts(
+    api_context: &ManagedApiContext,
+    count_per_account: u32,
+) -> anyhow::Result<HashMap<MonetaryAccountId, Vec<Payment>>> {
+    let count_per_account = validate_count(count_per_account)?;
+
+    let monetary_accounts = get_monetary_accounts(api_context).await?;
+
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS));
+    let mut requests = JoinSet::new();
+
+    for monetary_account in monetary_accounts {
+        let monetary_account_id = monetary_account.get_id();
+        let api_context = api_context.clone();
+        let semaphore = semaphore.clone();
+
+        requests.spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+
+            let payments =
+                get_payments_with_count(&api_context, monetary_account_id, count_per_account)
+                    .await?;
+
+            anyhow::Ok((monetary_account_id, payments))
+        });
+    }
+
+    let mut payments_per_account = HashMap::new();
+
+    while let Some(result) = requests.join_next().await {
+        let (monetary_account_id, payments) = result??;
+        payments_per_account.insert(monetary_account_id, payments);
+    }
+
+    Ok(payments_per_account)
+}
+
+/// Get the most recent payments of an account, oldest first, e.g. for reporting
+pub async fn get_payments_chronological(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+) -> anyhow::Result<Vec<Payment>> {
+    let mut payments = get_payments(api_context, monetary_account_id, None).await?;
+    payments.reverse();
+
+    Ok(payments)
+}
+
+/// Get a compact view of the payments of an account, for when only the running balance
+/// is of interest. bunq has no separate endpoint for this, but only the fields of a [`Mutation`]
+/// are parsed, so the counterparty details don't need to be processed.
+pub async fn get_mutations(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    list_params: Option<ListParams>,
+) -> anyhow::Result<Vec<Mutation>> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = list_url(
+        &format!(
+            "{}/user/{}/monetary-account/{}/payment",
+            base_url, user_id, monetary_account_id.0
+        ),
+        list_params,
+    )?;
+
+    #[derive(Deserialize, Debug)]
+    struct MutationWrapper {
+        #[serde(rename = "Payment")]
+        mutation: Mutation,
+    }
+
+    Ok(send_request::<MutationWrapper>(client.get(url))
+        .await?
+        .into_iter()
+        .map(|entry| entry.mutation)
+        .collect())
+}
+
+/// Download the content of a file attached to a payment, such as a receipt.
+/// bunq keeps attachments per account, so the account of the [`AttachmentReference`] is
+/// used rather than the payment itself.
+pub async fn get_payment_attachment(
+    api_context: &ManagedApiContext,
+    attachment: &AttachmentReference,
+) -> anyhow::Result<Vec<u8>> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
 
     let client = get_authenticated_client(api_context).await?;
 
     let url = format!(
-        "{}/user/{}/monetary-account/{}/payment?count=200",
-        BUNQ_BASE_URL, user_id, monetary_account_id.0
+        "{}/user/{}/monetary-account/{}/attachment/{}/content",
+        base_url, user_id, attachment.monetary_account_id.0, attachment.id
     );
 
-    let response_result = client.get(url).send().await?.text().await?;
+    send_request_for_bytes(client.get(url)).await
+}
+
+/// Pay an amount from one of the user's accounts to an IBAN.
+///
+/// The amount is the amount to transfer and is always sent as a positive value, as bunq
+/// expects. Payments that are fetched show money leaving the account as a negative amount,
+/// so the amount of such a payment can be passed as is, e.g. to pay it back.
+pub async fn create_payment(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    amount: Amount,
+    counterparty_iban: &str,
+    counterparty_name: &str,
+    description: &str,
+) -> anyhow::Result<PaymentId> {
+    create_payment_with_idempotency_key(
+        api_context,
+        monetary_account_id,
+        amount,
+        counterparty_iban,
+        counterparty_name,
+        description,
+        None,
+    )
+    .await
+}
+
+/// Make a payment like [`create_payment`], with a key to be able to safely retry it: bunq
+/// makes the payment only once for all requests with the same key
+pub async fn create_payment_with_idempotency_key(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    amount: Amount,
+    counterparty_iban: &str,
+    counterparty_name: &str,
+    description: &str,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> anyhow::Result<PaymentId> {
+    validate_description(description)?;
+
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/payment",
+        base_url, user_id, monetary_account_id.0
+    );
+
+    let payload = PaymentPayload::from(PaymentRequest {
+        amount,
+        counterparty_iban: counterparty_iban.to_string(),
+        counterparty_name: counterparty_name.to_string(),
+        description: description.to_string(),
+    });
+
+    let body = serde_json::to_string(&payload)?;
+
+    send_request::<CreatedIdWrapper>(client.post(url).idempotency_key(idempotency_key).body(body))
+        .await?
+        .into_iter()
+        .map(|content| PaymentId(content.id.id))
+        .next()
+        .ok_or(anyhow!("Id not found in response"))
+}
+
+/// Send several payments from an account at once. The amounts are sent as positive values,
+/// like [`create_payment`] does. bunq can make some of the payments while refusing others, in
+/// which case the batch is returned with the payments that were made and the errors of the
+/// ones that weren't. Only when bunq didn't make the batch at all an error is returned.
+/// Pass an idempotency key to be able to safely retry the batch, like
+/// [`create_payment_with_idempotency_key`]. Once the batch is made its id is always returned,
+/// even when fetching its payments fails afterwards, so it's never made again by mistake.
+pub async fn create_payment_batch(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    payments: Vec<PaymentRequest>,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> anyhow::Result<PaymentBatch> {
+    if payments.is_empty() {
+        return Err(anyhow!("A payment batch needs at least one payment"));
+    }
+
+    for payment in &payments {
+        validate_description(&payment.description)?;
+    }
+
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/payment-batch",
+        base_url, user_id, monetary_account_id.0
+    );
+
+    #[derive(Serialize, Debug)]
+    struct Payload {
+        payments: Vec<PaymentPayload>,
+    }
+
+    let payload = Payload {
+        payments: payments.into_iter().map(PaymentPayload::from).collect(),
+    };
+
+    let body = serde_json::to_string(&payload)?;
+
+    let (created, errors) = send_request_allowing_errors::<CreatedIdWrapper>(
+        client
+            .post(&url)
+            .body(body)
+            .idempotency_key(idempotency_key),
+    )
+    .await?;
+
+    let payment_batch_id = created
+        .into_iter()
+        .map(|content| PaymentBatchId(content.id.id))
+        .next()
+        .ok_or(anyhow!("Id not found in response"))?;
+
+    for error in &errors {
+        warn!(
+            "bunq refused a payment of batch {}: {}",
+            payment_batch_id.0, error
+        );
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct PaymentBatchWrapper {
+        #[serde(rename = "PaymentBatch")]
+        payment_batch: PaymentBatchContent,
+    }
 
     #[derive(Deserialize, Debug)]
-    struct PaymentWrapper {
+    struct PaymentBatchContent {
+        payments: PaymentBatchPayments,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct PaymentBatchPayments {
         #[serde(rename = "Payment")]
-        payment: Payment,
+        payment: Vec<Payment>,
+    }
+
+    let url = format!("{}/{}", url, payment_batch_id.0);
+
+    let payments = send_request::<PaymentBatchWrapper>(client.get(url))
+        .await
+        .and_then(|entries| {
+            entries
+                .into_iter()
+                .map(|entry| entry.payment_batch.payments.payment)
+                .next()
+                .ok_or(anyhow!("Payment batch not found in response"))
+        });
+
+    Ok(PaymentBatch {
+        id: payment_batch_id,
+        errors,
+        payments: payments
+            .inspect_err(|e| {
+                warn!(
+                    "Payment batch {} was made, but fetching its payments failed: {:#}",
+                    payment_batch_id.0, e
+                )
+            })
+            .ok(),
+    })
+}
+
+#[derive(Deserialize, Debug)]
+struct CreatedIdWrapper {
+    #[serde(rename = "Id")]
+    id: CreatedId,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreatedId {
+    id: u64,
+}
+
+/// The body of a payment as bunq expects it when creating one
+#[derive(Serialize, Debug)]
+struct PaymentPayload {
+    amount: Amount,
+    counterparty_alias: Alias,
+    description: String,
+}
+
+impl From<PaymentRequest> for PaymentPayload {
+    fn from(request: PaymentRequest) -> Self {
+        PaymentPayload {
+            amount: outgoing_amount(request.amount),
+            counterparty_alias: Alias {
+                r#type: AliasType::Iban,
+                value: request.counterparty_iban,
+                name: Some(request.counterparty_name),
+            },
+            description: request.description,
+        }
+    }
+}
+
+/// bunq rejects payments of which the description is longer than 140 characters
+const MAX_DESCRIPTION_LENGTH: usize = 140;
+
+/// Check the description fits in a payment before sending it
+fn validate_description(description: &str) -> Result<(), BunqyyError> {
+    let length = description.chars().count();
+
+    if length > MAX_DESCRIPTION_LENGTH {
+        return Err(BunqyyError::DescriptionTooLong(length));
+    }
+
+    Ok(())
+}
+
+/// The amount to send for an outgoing payment, which bunq expects to be positive
+fn outgoing_amount(amount: Amount) -> Amount {
+    Amount {
+        value: amount.value.abs(),
+        ..amount
+    }
+}
+
+/// Get the current state of a draft payment, e.g. to find out whether it was accepted
+pub async fn get_draft_payment(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    draft_payment_id: DraftPaymentId,
+) -> anyhow::Result<DraftPayment> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/draft-payment/{}",
+        base_url, user_id, monetary_account_id.0, draft_payment_id.0
+    );
+
+    #[derive(Deserialize, Debug)]
+    struct DraftPaymentWrapper {
+        #[serde(rename = "DraftPayment")]
+        draft_payment: DraftPayment,
     }
 
-    let content = process_response_content::<PaymentWrapper>(response_result.as_str())?;
+    send_request::<DraftPaymentWrapper>(client.get(url))
+        .await?
+        .into_iter()
+        .map(|entry| entry.draft_payment)
+        .next()
+        .ok_or(anyhow!("Draft payment not found in response"))
+}
+
+/// Get the current state of a scheduled payment, e.g. to find out whether it's still active
+pub async fn get_scheduled_payment(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    scheduled_payment_id: ScheduledPaymentId,
+) -> anyhow::Result<ScheduledPayment> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
 
-    match content {
-        BunqResponse::Success(content) => Ok(content
-            .response
-            .into_iter()
-            .map(move |entry| entry.payment)
-            .collect()),
-        BunqResponse::Error(errors) => Err(anyhow!("Error: {:?}", errors.error)),
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/schedule-payment/{}",
+        base_url, user_id, monetary_account_id.0, scheduled_payment_id.0
+    );
+
+    #[derive(Deserialize, Debug)]
+    struct ScheduledPaymentWrapper {
+        #[serde(rename = "SchedulePayment")]
+        scheduled_payment: ScheduledPayment,
     }
+
+    send_request::<ScheduledPaymentWrapper>(client.get(url))
+        .await?
+        .into_iter()
+        .map(|entry| entry.scheduled_payment)
+        .next()
+        .ok_or(anyhow!("Scheduled payment not found in response"))
+}
+
+#[derive(Deserialize, Debug)]
+struct PaymentWrapper {
+    #[serde(rename = "Payment")]
+    payment: Payment,
 }
 
-#[derive(Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct PaymentId(pub u64);
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
 pub struct Payment {
     pub id: PaymentId,
-    pub created: String,
+    #[serde(with = "bunq_timestamp")]
+    pub created: DateTime<Utc>,
     pub monetary_account_id: MonetaryAccountId,
+    /// The amount as seen from the account, negative when money left it
     pub amount: Amount,
-    pub alias: LabelMonetaryAccount,
-    pub counterparty_alias: LabelMonetaryAccount,
+    /// bunq leaves out the aliases and balance for some kinds of payments, such as interest
+    /// and transfers to savings accounts
+    #[serde(default)]
+    pub alias: Option<LabelMonetaryAccount>,
+    #[serde(default)]
+    pub counterparty_alias: Option<LabelMonetaryAccount>,
     pub description: String,
     pub r#type: String,
     pub sub_type: String,
     pub merchant_reference: Option<String>,
-    pub balance_after_mutation: Amount,
+    #[serde(default)]
+    pub balance_after_mutation: Option<Amount>,
+    pub request_reference_split_the_bill: Option<Vec<RequestInquiryReference>>,
+    pub geolocation: Option<Geolocation>,
+    /// Receipts and other files attached to the payment, e.g. in the bunq app
+    #[serde(rename = "attachment", default)]
+    pub attachments: Vec<AttachmentReference>,
+}
+
+/// The total of the payments that left the account, as a positive amount per currency
+pub fn total_outgoing(payments: &[Payment]) -> Vec<Amount> {
+    total_per_currency(
+        payments
+            .iter()
+            .filter(|payment| payment.direction() == Direction::Outgoing),
+    )
+}
+
+/// The total of the payments that came into the account, per currency
+pub fn total_incoming(payments: &[Payment]) -> Vec<Amount> {
+    total_per_currency(payments.iter().filter(|payment| payment.is_incoming()))
+}
+
+/// The payments of which the amount is more than the threshold, regardless of their direction
+pub fn filter_above(payments: &[Payment], threshold: Decimal) -> Vec<&Payment> {
+    payments
+        .iter()
+        .filter(|payment| payment.amount.as_decimal().abs() > threshold)
+        .collect()
+}
+
+/// Sum the (absolute) amounts of the payments per currency, ordered by currency, so amounts
+/// in different currencies are never added up
+fn total_per_currency<'a>(payments: impl Iterator<Item = &'a Payment>) -> Vec<Amount> {
+    let mut totals = BTreeMap::<String, Decimal>::new();
+
+    for payment in payments {
+        *totals.entry(payment.amount.currency.clone()).or_default() +=
+            payment.amount.as_decimal().abs();
+    }
+
+    totals
+        .into_iter()
+        .map(|(currency, total)| Amount::new(total, currency))
+        .collect()
+}
+
+/// Whether money came into or left the account
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+impl Payment {
+    /// The direction of the payment, derived from the sign of its amount
+    pub fn direction(&self) -> Direction {
+        if self.amount.is_negative() {
+            Direction::Outgoing
+        } else {
+            Direction::Incoming
+        }
+    }
+
+    pub fn is_incoming(&self) -> bool {
+        self.direction() == Direction::Incoming
+    }
+
+    /// The requests that were made to split this payment with others
+    pub fn split_the_bill_references(&self) -> &[RequestInquiryReference] {
+        self.request_reference_split_the_bill
+            .as_deref()
+            .unwrap_or_default()
+    }
+
+    /// The other party of the payment as an alias that can be paid to, e.g. to pay them back
+    /// with [`create_payment`]. `None` when bunq doesn't know their IBAN, such as for card
+    /// payments at merchants.
+    pub fn counterparty(&self) -> Option<Alias> {
+        let counterparty_alias = self.counterparty_alias.as_ref()?;
+        let iban = counterparty_alias.iban.as_deref()?.trim();
+
+        if iban.is_empty() {
+            return None;
+        }
+
+        Some(Alias {
+            r#type: AliasType::Iban,
+            value: iban.to_string(),
+            name: Some(counterparty_alias.display_name.clone())
+                .filter(|display_name| !display_name.is_empty()),
+        })
+    }
+
+    /// Where the payment was made, when bunq knows it
+    pub fn geolocation(&self) -> Option<&Geolocation> {
+        self.geolocation.as_ref()
+    }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl Transaction for Payment {
+    fn amount(&self) -> &Amount {
+        &self.amount
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    fn counterparty_name(&self) -> &str {
+        self.counterparty_alias
+            .as_ref()
+            .map_or("", |alias| alias.display_name.as_str())
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// A payment to make as part of a batch, see [`create_payment_batch`]
+#[derive(Debug, Clone)]
+pub struct PaymentRequest {
+    pub amount: Amount,
+    pub counterparty_iban: String,
+    pub counterparty_name: String,
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct PaymentBatchId(pub u64);
+
+/// Payments that were made together, see [`create_payment_batch`]
+#[derive(Debug, Clone)]
+pub struct PaymentBatch {
+    pub id: PaymentBatchId,
+    /// The payments of the batch, `None` when the batch was made but fetching them failed
+    pub payments: Option<Vec<Payment>>,
+    /// Why bunq refused the payments of the batch it didn't make, empty when it made them all
+    pub errors: Vec<BunqError>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
 pub struct LabelMonetaryAccount {
     pub iban: Option<String>,
+    #[serde(default)]
     pub display_name: String,
-    pub country: String,
+    #[serde(default)]
+    pub country: Option<String>,
+}
+
+/// A lightweight representation of a payment, only describing how it changed the balance
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Mutation {
+    pub id: PaymentId,
+    pub created: String,
+    pub amount: Amount,
+    #[serde(default)]
+    pub balance_after_mutation: Option<Amount>,
+    pub r#type: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct DraftPaymentId(pub u64);
+
+/// A payment that only executes once enough of the account's users accepted it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct DraftPayment {
+    pub id: DraftPaymentId,
+    /// e.g. `PENDING`, `ACCEPTED`, `REJECTED` or `CANCELLED`
+    pub status: String,
+    pub number_of_required_accepts: u32,
+    #[serde(default)]
+    pub responses: Vec<DraftPaymentResponse>,
+    /// The payment that was made once the draft was accepted, e.g. `{"Payment": {..}}`
+    pub object: Option<serde_json::Value>,
+}
+
+impl DraftPayment {
+    /// How many users still have to accept the draft before it's executed
+    pub fn remaining_accepts(&self) -> u32 {
+        let accepted = self
+            .responses
+            .iter()
+            .filter(|response| response.status == "ACCEPTED")
+            .count() as u32;
+
+        self.number_of_required_accepts.saturating_sub(accepted)
+    }
+
+    /// The id of the payment the draft resulted in, once it's executed
+    pub fn payment_id(&self) -> Option<PaymentId> {
+        self.object
+            .as_ref()?
+            .get("Payment")?
+            .get("id")?
+            .as_u64()
+            .map(PaymentId)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct DraftPaymentResponse {
+    pub status: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct ScheduledPaymentId(pub u64);
+
+/// A payment that's executed on a schedule, either once or recurring
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct ScheduledPayment {
+    pub schedule: Schedule,
+}
+
+impl ScheduledPayment {
+    /// e.g. `ACTIVE`, `FINISHED` or `CANCELLED`
+    pub fn status(&self) -> &str {
+        &self.schedule.status
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct Schedule {
+    #[serde(with = "bunq_timestamp")]
+    pub time_start: DateTime<Utc>,
+    #[serde(with = "bunq_timestamp::option")]
+    pub time_end: Option<DateTime<Utc>>,
+    /// e.g. `ONCE`, `DAILY`, `WEEKLY`, `MONTHLY` or `YEARLY`
+    pub recurrence_unit: String,
+    pub recurrence_size: u32,
+    pub status: String,
+}
+
+/// A reference to a request that was made as part of splitting a payment
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct RequestInquiryReference {
+    pub r#type: String,
+    pub id: u64,
+}
+
+/// A file attached to a payment, see [`get_payment_attachment`] for its content
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct AttachmentReference {
+    pub id: u64,
+    pub monetary_account_id: MonetaryAccountId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct Geolocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+    pub radius: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{process_response_content, BunqResponse};
+    use std::str::FromStr;
+
+    const SPLIT_THE_BILL_PAYMENT: &str = r#"
+    {
+        "Response": [
+            {
+                "Payment": {
+                    "id": 42,
+                    "created": "2024-10-14 21:19:14.426211",
+                    "monetary_account_id": 7,
+                    "amount": { "currency": "EUR", "value": "-30.00" },
+                    "alias": { "iban": "NL00BUNQ0000000001", "display_name": "Me", "country": "NL" },
+                    "counterparty_alias": { "iban": null, "display_name": "Restaurant", "country": "NL" },
+                    "description": "Dinner",
+                    "type": "MASTERCARD",
+                    "sub_type": "PAYMENT",
+                    "merchant_reference": null,
+                    "balance_after_mutation": { "currency": "EUR", "value": "70.00" },
+                    "request_reference_split_the_bill": [
+                        { "type": "RequestInquiry", "id": 1001 },
+                        { "type": "RequestInquiry", "id": 1002 }
+                    ],
+                    "geolocation": {
+                        "latitude": 52.3676,
+                        "longitude": 4.9041,
+                        "altitude": 0.0,
+                        "radius": 65.0
+                    }
+                }
+            }
+        ]
+    }
+    "#;
+
+    fn parse_payments(response: &str) -> Vec<Payment> {
+        match process_response_content::<PaymentWrapper>(response).unwrap() {
+            BunqResponse::Success(content) => content
+                .response
+                .into_iter()
+                .map(|entry| entry.payment)
+                .collect(),
+            BunqResponse::Error(_) => panic!("Expected success"),
+        }
+    }
+
+    #[test]
+    fn split_the_bill_payment_is_deserialized() {
+        let payment = parse_payments(SPLIT_THE_BILL_PAYMENT).remove(0);
+
+        assert_eq!(
+            bunq_timestamp::format(&payment.created),
+            "2024-10-14 21:19:14.426211"
+        );
+
+        let request_ids: Vec<u64> = payment
+            .split_the_bill_references()
+            .iter()
+            .map(|reference| reference.id)
+            .collect();
+
+        assert_eq!(request_ids, vec![1001, 1002]);
+        assert_eq!(payment.geolocation().unwrap().latitude, 52.3676);
+    }
+
+    #[test]
+    fn savings_transfer_without_balance_is_deserialized() {
+        let response = r#"
+        {
+            "Response": [
+                {
+                    "Payment": {
+                        "id": 43,
+                        "created": "2024-10-15 00:00:01.000000",
+                        "monetary_account_id": 8,
+                        "amount": { "currency": "EUR", "value": "25.00" },
+                        "alias": { "iban": "NL00BUNQ0000000003", "display_name": "Savings" },
+                        "description": "Automatic savings",
+                        "type": "BUNQ",
+                        "sub_type": "SAVINGS",
+                        "merchant_reference": null,
+                        "request_reference_split_the_bill": null,
+                        "geolocation": null
+                    }
+                }
+            ]
+        }
+        "#;
+
+        let payment = parse_payments(response).remove(0);
+
+        assert!(payment.balance_after_mutation.is_none());
+        assert!(payment.counterparty_alias.is_none());
+        assert_eq!(payment.counterparty_name(), "");
+        assert_eq!(payment.alias.unwrap().country, None);
+    }
+
+    #[test]
+    fn long_descriptions_are_rejected() {
+        assert!(validate_description(&"a".repeat(140)).is_ok());
+        assert!(validate_description(&"é".repeat(140)).is_ok());
+        assert!(matches!(
+            validate_description(&"a".repeat(141)),
+            Err(BunqyyError::DescriptionTooLong(141))
+        ));
+    }
+
+    #[test]
+    fn counterparty_can_be_paid_when_its_iban_is_known() {
+        let mut payment = parse_payments(SPLIT_THE_BILL_PAYMENT).remove(0);
+        assert!(payment.counterparty().is_none());
+
+        payment.counterparty_alias = Some(LabelMonetaryAccount {
+            iban: Some("NL00BANK0000000002".to_string()),
+            display_name: "Friend".to_string(),
+            country: Some("NL".to_string()),
+        });
+
+        let counterparty = payment.counterparty().unwrap();
+        assert_eq!(counterparty.r#type, AliasType::Iban);
+        assert_eq!(counterparty.value, "NL00BANK0000000002");
+        assert_eq!(counterparty.name.as_deref(), Some("Friend"));
+    }
+
+    #[test]
+    fn payment_round_trips_through_serialization() {
+        let payment = parse_payments(SPLIT_THE_BILL_PAYMENT).remove(0);
+
+        let serialized = serde_json::to_string(&payment).unwrap();
+        let deserialized: Payment = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized.id.0, 42);
+        assert_eq!(deserialized.amount.value.to_string(), "-30.00");
+        assert_eq!(deserialized.counterparty_name(), "Restaurant");
+        assert_eq!(deserialized.split_the_bill_references().len(), 2);
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), serialized);
+    }
+
+    #[test]
+    fn draft_payment_counts_remaining_accepts() {
+        let draft_payment: DraftPayment = serde_json::from_str(
+            r#"{
+                "id": 5,
+                "status": "PENDING",
+                "number_of_required_accepts": 2,
+                "responses": [{ "status": "ACCEPTED" }, { "status": "PENDING" }],
+                "object": null
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(draft_payment.remaining_accepts(), 1);
+        assert!(draft_payment.payment_id().is_none());
+    }
+
+    #[test]
+    fn executed_draft_payment_has_a_payment_id() {
+        let draft_payment: DraftPayment = serde_json::from_str(
+            r#"{
+                "id": 5,
+                "status": "ACCEPTED",
+                "number_of_required_accepts": 1,
+                "responses": [{ "status": "ACCEPTED" }],
+                "object": { "Payment": { "id": 42 } }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(draft_payment.remaining_accepts(), 0);
+        assert_eq!(draft_payment.payment_id().unwrap().0, 42);
+    }
+
+    #[test]
+    fn payment_request_is_sent_with_iban_alias() {
+        let payload = PaymentPayload::from(PaymentRequest {
+            amount: Amount::new(Decimal::new(-750, 2), "EUR"),
+            counterparty_iban: "NL00BANK0000000002".to_string(),
+            counterparty_name: "Friend".to_string(),
+            description: "Lunch".to_string(),
+        });
+
+        assert_eq!(
+            serde_json::to_value(&payload).unwrap(),
+            serde_json::json!({
+                "amount": { "currency": "EUR", "value": "7.50" },
+                "counterparty_alias": { "type": "IBAN", "value": "NL00BANK0000000002", "name": "Friend" },
+                "description": "Lunch"
+            })
+        );
+    }
+
+    #[test]
+    fn positive_amount_is_sent_as_is() {
+        let amount = outgoing_amount(Amount::new(Decimal::new(1250, 2), "EUR"));
+
+        assert_eq!(amount.value.to_string(), "12.50");
+        assert_eq!(amount.currency, "EUR");
+    }
+
+    #[test]
+    fn negative_amount_is_sent_as_positive() {
+        let payment = parse_payments(SPLIT_THE_BILL_PAYMENT).remove(0);
+
+        let amount = outgoing_amount(payment.amount);
+
+        assert_eq!(amount.value.to_string(), "30.00");
+    }
+
+    #[test]
+    fn malformed_payment_does_not_drop_the_others() {
+        let mut response: Value = serde_json::from_str(SPLIT_THE_BILL_PAYMENT).unwrap();
+        let good = response["Response"][0].clone();
+        let mut bad = good.clone();
+        bad["Payment"]["amount"] = Value::String("not an amount".to_string());
+        response["Response"] = Value::Array(vec![good.clone(), bad, good]);
+
+        let entries = match process_response_content::<Value>(&response.to_string()).unwrap() {
+            BunqResponse::Success(content) => content.response,
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        let payments = deserialize_entries::<PaymentWrapper>(entries);
+
+        assert_eq!(payments.len(), 2);
+        assert_eq!(payments[0].payment.description, "Dinner");
+    }
+
+    fn payment_of(value: &str, currency: &str) -> Payment {
+        let mut payment = parse_payments(SPLIT_THE_BILL_PAYMENT).remove(0);
+        payment.amount = Amount::new(Decimal::from_str(value).unwrap(), currency);
+
+        payment
+    }
+
+    #[test]
+    fn totals_are_summed_per_currency() {
+        let payments = [
+            payment_of("-30.00", "EUR"),
+            payment_of("-12.50", "EUR"),
+            payment_of("-5.00", "USD"),
+            payment_of("100.00", "EUR"),
+        ];
+
+        let outgoing = total_outgoing(&payments);
+        assert_eq!(outgoing.len(), 2);
+        assert_eq!(outgoing[0].to_string(), "42.50 EUR");
+        assert_eq!(outgoing[1].to_string(), "5.00 USD");
+
+        let incoming = total_incoming(&payments);
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].to_string(), "100.00 EUR");
+    }
+
+    #[test]
+    fn payments_are_filtered_by_their_absolute_amount() {
+        let payments = [
+            payment_of("-30.00", "EUR"),
+            payment_of("-12.50", "EUR"),
+            payment_of("20.00", "EUR"),
+        ];
+
+        let above = filter_above(&payments, Decimal::from(15));
+
+        assert_eq!(above.len(), 2);
+        assert_eq!(above[0].amount.value.to_string(), "-30.00");
+        assert_eq!(above[1].amount.value.to_string(), "20.00");
+    }
+
+    #[test]
+    fn sent_payment_is_outgoing() {
+        let payment = parse_payments(SPLIT_THE_BILL_PAYMENT).remove(0);
+
+        assert_eq!(payment.direction(), Direction::Outgoing);
+        assert!(!payment.is_incoming());
+    }
+
+    #[test]
+    fn received_payment_is_incoming() {
+        let response = SPLIT_THE_BILL_PAYMENT.replace(r#""-30.00""#, r#""30.00""#);
+
+        let payment = parse_payments(&response).remove(0);
+
+        assert_eq!(payment.direction(), Direction::Incoming);
+        assert!(payment.is_incoming());
+    }
+
+    #[test]
+    fn payment_without_split_the_bill_is_deserialized() {
+        let mut response: serde_json::Value = serde_json::from_str(SPLIT_THE_BILL_PAYMENT).unwrap();
+        let payment = response["Response"][0]["Payment"].as_object_mut().unwrap();
+        payment.remove("request_reference_split_the_bill");
+        payment.remove("geolocation");
+        let response = response.to_string();
+
+        let payment = parse_payments(&response).remove(0);
+
+        assert!(payment.split_the_bill_references().is_empty());
+        assert!(payment.geolocation().is_none());
+        assert!(payment.attachments.is_empty());
+    }
+
+    #[test]
+    fn payment_attachments_are_deserialized() {
+        let mut response: Value = serde_json::from_str(SPLIT_THE_BILL_PAYMENT).unwrap();
+        response["Response"][0]["Payment"]["attachment"] =
+            serde_json::json!([{ "id": 12, "monetary_account_id": 7 }]);
+
+        let payment = parse_payments(&response.to_string()).remove(0);
+
+        assert_eq!(payment.attachments.len(), 1);
+        assert_eq!(payment.attachments[0].id, 12);
+        assert_eq!(
+            payment.attachments[0].monetary_account_id,
+            MonetaryAccountId(7)
+        );
+    }
 }
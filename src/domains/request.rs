@@ -0,0 +1,184 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::api_context::ManagedApiContext;
+use crate::bunq_timestamp;
+use crate::common::{list_url, ListParams};
+use crate::domains::monetary_account::{Amount, MonetaryAccountId};
+use crate::domains::payment::LabelMonetaryAccount;
+use crate::http::{get_authenticated_client, send_request};
+
+/// Get the requests for money others made to an account
+pub async fn get_request_responses(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    list_params: Option<ListParams>,
+) -> anyhow::Result<Vec<RequestResponse>> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = list_url(
+        &format!(
+            "{}/user/{}/monetary-account/{}/request-response",
+            base_url, user_id, monetary_account_id.0
+        ),
+        list_params,
+    )?;
+
+    Ok(send_request::<RequestResponseWrapper>(client.get(url))
+        .await?
+        .into_iter()
+        .map(|entry| entry.request_response)
+        .collect())
+}
+
+/// Accept or reject a request for money. Accepting pays the requested amount.
+/// Returns the request as it is after responding to it.
+pub async fn respond_to_request(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    request_response_id: RequestResponseId,
+    accept: bool,
+) -> anyhow::Result<RequestResponse> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/request-response/{}",
+        base_url, user_id, monetary_account_id.0, request_response_id.0
+    );
+
+    #[derive(Serialize, Debug)]
+    struct Payload {
+        status: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        amount_responded: Option<Amount>,
+    }
+
+    // bunq needs to know how much is paid when accepting, which is what was requested
+    let payload = if accept {
+        let request_response = get_request_response(&client, &url).await?;
+
+        Payload {
+            status: "ACCEPTED",
+            amount_responded: Some(request_response.amount_inquired),
+        }
+    } else {
+        Payload {
+            status: "REJECTED",
+            amount_responded: None,
+        }
+    };
+
+    let body = serde_json::to_string(&payload)?;
+
+    send_request::<serde_json::Value>(client.put(&url).body(body)).await?;
+
+    get_request_response(&client, &url).await
+}
+
+async fn get_request_response(
+    client: &reqwest_middleware::ClientWithMiddleware,
+    url: &str,
+) -> anyhow::Result<RequestResponse> {
+    send_request::<RequestResponseWrapper>(client.get(url))
+        .await?
+        .into_iter()
+        .map(|entry| entry.request_response)
+        .next()
+        .ok_or(anyhow!("Request not found in response"))
+}
+
+#[derive(Deserialize, Debug)]
+struct RequestResponseWrapper {
+    #[serde(rename = "RequestResponse")]
+    request_response: RequestResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct RequestResponseId(pub u64);
+
+/// A request for money someone made to the user
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct RequestResponse {
+    pub id: RequestResponseId,
+    #[serde(with = "bunq_timestamp")]
+    pub created: DateTime<Utc>,
+    pub monetary_account_id: MonetaryAccountId,
+    /// The amount that was requested
+    pub amount_inquired: Amount,
+    /// The amount that was paid, once the request is accepted
+    pub amount_responded: Option<Amount>,
+    /// Who made the request
+    pub counterparty_alias: LabelMonetaryAccount,
+    pub description: String,
+    /// e.g. `PENDING`, `ACCEPTED`, `REJECTED` or `REVOKED`
+    pub status: String,
+}
+
+impl RequestResponse {
+    pub fn is_pending(&self) -> bool {
+        self.status == "PENDING"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{process_response_content, BunqResponse};
+
+    #[test]
+    fn request_response_is_deserialized() {
+        let response = r#"
+        {
+            "Response": [
+                {
+                    "RequestResponse": {
+                        "id": 9,
+                        "created": "2024-10-14 21:19:14.426211",
+                        "monetary_account_id": 7,
+                        "amount_inquired": { "currency": "EUR", "value": "12.50" },
+                        "amount_responded": null,
+                        "counterparty_alias": { "iban": "NL00BANK0000000002", "display_name": "Friend", "country": "NL" },
+                        "description": "Cinema",
+                        "status": "PENDING"
+                    }
+                }
+            ]
+        }
+        "#;
+
+        let request_response =
+            match process_response_content::<RequestResponseWrapper>(response).unwrap() {
+                BunqResponse::Success(content) => {
+                    content
+                        .response
+                        .into_iter()
+                        .next()
+                        .unwrap()
+                        .request_response
+                }
+                BunqResponse::Error(_) => panic!("Expected success"),
+            };
+
+        assert!(request_response.is_pending());
+        assert_eq!(request_response.amount_inquired.value.to_string(), "12.50");
+        assert_eq!(request_response.counterparty_alias.display_name, "Friend");
+    }
+}
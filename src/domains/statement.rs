@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Context};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::api_context::ManagedApiContext;
+use crate::common::BunqyyError;
+use crate::domains::monetary_account::MonetaryAccountId;
+use crate::http::{get_authenticated_client, send_request, send_request_for_bytes};
+
+/// Have bunq generate a CSV statement of an account for the given (inclusive) period.
+/// The statement is generated asynchronously, its content is available through
+/// [`get_statement_transactions`] once bunq finishes it.
+pub async fn create_statement(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    date_start: NaiveDate,
+    date_end: NaiveDate,
+) -> anyhow::Result<StatementId> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/customer-statement",
+        base_url, user_id, monetary_account_id.0
+    );
+
+    #[derive(Serialize)]
+    struct Payload {
+        statement_format: &'static str,
+        date_start: String,
+        date_end: String,
+        regional_format: &'static str,
+    }
+
+    let body = serde_json::to_string(&Payload {
+        statement_format: "CSV",
+        date_start: date_start.format("%Y-%m-%d").to_string(),
+        date_end: date_end.format("%Y-%m-%d").to_string(),
+        // Uses a dot as decimal separator, which is what the amounts are parsed with
+        regional_format: "UK_US",
+    })?;
+
+    #[derive(Deserialize, Debug)]
+    struct Content {
+        #[serde(rename = "Id")]
+        id: StatementId,
+    }
+
+    send_request::<Content>(client.post(url).body(body))
+        .await?
+        .into_iter()
+        .map(|content| content.id)
+        .next()
+        .ok_or(anyhow!("Id not found in response"))
+}
+
+/// Download a CSV statement created with [`create_statement`] and parse its lines
+pub async fn get_statement_transactions(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    statement_id: StatementId,
+) -> anyhow::Result<Vec<StatementLine>> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/customer-statement/{}/content",
+        base_url, user_id, monetary_account_id.0, statement_id.id
+    );
+
+    let content = send_request_for_bytes(client.get(url)).await?;
+    let content = std::str::from_utf8(&content).with_context(|| "Statement is not valid UTF-8")?;
+
+    Ok(parse_statement_csv(content)?)
+}
+
+/// Parse the lines of a CSV statement as generated by bunq
+fn parse_statement_csv(content: &str) -> Result<Vec<StatementLine>, BunqyyError> {
+    csv::Reader::from_reader(content.as_bytes())
+        .deserialize::<StatementLine>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| BunqyyError::CsvError(e.to_string()))
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct StatementId {
+    pub id: u64,
+}
+
+/// A single transaction on a statement
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatementLine {
+    #[serde(rename = "Date")]
+    pub date: NaiveDate,
+    /// The amount in the currency of the account, negative for outgoing transactions
+    #[serde(rename = "Amount")]
+    pub amount: Decimal,
+    #[serde(rename = "Counterparty")]
+    pub counterparty_iban: String,
+    #[serde(rename = "Name")]
+    pub counterparty_name: String,
+    #[serde(rename = "Description")]
+    pub description: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn statement_csv_is_parsed_into_lines() {
+        let content = "\"Date\",\"Interest Date\",\"Amount\",\"Account\",\"Counterparty\",\"Name\",\"Description\"\n\
+            \"2024-10-14\",\"2024-10-14\",\"-4.50\",\"NL00BUNQ0000000001\",\"NL00BANK0000000002\",\"Bakery\",\"Bread, twice\"\n\
+            \"2024-10-15\",\"2024-10-15\",\"1250.00\",\"NL00BUNQ0000000001\",\"NL00BANK0000000003\",\"Employer\",\"Salary\"\n";
+
+        let lines = parse_statement_csv(content).unwrap();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0].date,
+            NaiveDate::from_ymd_opt(2024, 10, 14).unwrap()
+        );
+        assert_eq!(lines[0].amount, Decimal::from_str("-4.50").unwrap());
+        assert_eq!(lines[0].counterparty_name, "Bakery");
+        assert_eq!(lines[0].description, "Bread, twice");
+        assert_eq!(lines[1].counterparty_iban, "NL00BANK0000000003");
+    }
+
+    #[test]
+    fn malformed_statement_is_a_csv_error() {
+        let content = "\"Date\",\"Amount\",\"Counterparty\",\"Name\",\"Description\"\n\
+            \"not a date\",\"1.00\",\"\",\"\",\"\"\n";
+
+        assert!(matches!(
+            parse_statement_csv(content),
+            Err(BunqyyError::CsvError(_))
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api_context::ManagedApiContext;
+use crate::domains::monetary_account::{Amount, MonetaryAccountId};
+use crate::http::{get_authenticated_client, send_request, send_request_for_bytes};
+
+/// Create a tab that can be paid once, e.g. to collect a payment in person through a QR code.
+/// Tabs belong to a cash register of the monetary account. bunq requires a tab to be created
+/// as open before it can be paid, so it's opened for payment right after. The returned
+/// [`Tab`] contains what's needed to render a QR code, or use [`get_tab_qr`] to have bunq
+/// render it.
+pub async fn create_tab(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    cash_register_id: CashRegisterId,
+    amount: Amount,
+    description: &str,
+) -> anyhow::Result<Tab> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/cash-register/{}/tab-usage-single",
+        base_url, user_id, monetary_account_id.0, cash_register_id.0
+    );
+
+    #[derive(Serialize, Debug)]
+    struct CreatePayload<'a> {
+        description: &'a str,
+        status: &'static str,
+        amount_total: Amount,
+    }
+
+    let body = serde_json::to_string(&CreatePayload {
+        description,
+        status: "OPEN",
+        amount_total: amount,
+    })?;
+
+    #[derive(Deserialize, Debug)]
+    struct Content {
+        #[serde(rename = "Uuid")]
+        uuid: TabUuid,
+    }
+
+    let uuid = send_request::<Content>(client.post(&url).body(body))
+        .await?
+        .into_iter()
+        .map(|content| content.uuid.uuid)
+        .next()
+        .ok_or(anyhow!("Uuid not found in response"))?;
+
+    let url = format!("{}/{}", url, uuid);
+
+    #[derive(Serialize, Debug)]
+    struct UpdatePayload {
+        status: &'static str,
+    }
+
+    let body = serde_json::to_string(&UpdatePayload {
+        status: "WAITING_FOR_PAYMENT",
+    })?;
+
+    send_request::<Value>(client.put(&url).body(body)).await?;
+
+    send_request::<TabWrapper>(client.get(&url))
+        .await?
+        .into_iter()
+        .map(|content| content.tab)
+        .next()
+        .ok_or(anyhow!("Tab not found in response"))
+}
+
+/// Get the QR code of a tab as a PNG image, as rendered by bunq
+pub async fn get_tab_qr(
+    api_context: &ManagedApiContext,
+    monetary_account_id: MonetaryAccountId,
+    cash_register_id: CashRegisterId,
+    tab_uuid: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!(
+        "{}/user/{}/monetary-account/{}/cash-register/{}/tab/{}/qr-code-content",
+        base_url, user_id, monetary_account_id.0, cash_register_id.0, tab_uuid
+    );
+
+    send_request_for_bytes(client.get(url)).await
+}
+
+#[derive(Deserialize, Debug)]
+struct TabWrapper {
+    #[serde(rename = "TabUsageSingle")]
+    tab: Tab,
+}
+
+/// The id of a cash register, which the tabs of a monetary account are created under
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CashRegisterId(pub u64);
+
+#[derive(Deserialize, Debug, Clone)]
+struct TabUuid {
+    uuid: String,
+}
+
+/// A tab that can be paid through a QR code or link
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "strict-deserialize", serde(deny_unknown_fields))]
+pub struct Tab {
+    pub uuid: String,
+    pub description: String,
+    pub status: String,
+    pub amount_total: Amount,
+    /// The token to encode in a QR code, which the bunq app can scan to pay the tab
+    pub qr_code_token: Option<String>,
+    /// The link through which the tab can be paid
+    pub tab_url: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::http::{process_response_content, BunqResponse};
+
+    #[test]
+    fn tab_is_deserialized() {
+        let response = r#"
+        {
+            "Response": [
+                {
+                    "TabUsageSingle": {
+                        "uuid": "3a9c4f0e-5d2b-4c8a-9e1f-7b6d2a0c8e41",
+                        "description": "Coffee",
+                        "status": "WAITING_FOR_PAYMENT",
+                        "amount_total": { "currency": "EUR", "value": "3.50" },
+                        "qr_code_token": "token",
+                        "tab_url": "https://bunq.me/t/3a9c4f0e"
+                    }
+                }
+            ]
+        }
+        "#;
+
+        let tab = match process_response_content::<TabWrapper>(response).unwrap() {
+            BunqResponse::Success(content) => content
+                .response
+                .into_iter()
+                .map(|entry| entry.tab)
+                .next()
+                .unwrap(),
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        assert_eq!(tab.uuid, "3a9c4f0e-5d2b-4c8a-9e1f-7b6d2a0c8e41");
+        assert_eq!(tab.status, "WAITING_FOR_PAYMENT");
+        assert_eq!(tab.amount_total.value.to_string(), "3.50");
+        assert_eq!(tab.qr_code_token.as_deref(), Some("token"));
+        assert_eq!(tab.tab_url.as_deref(), Some("https://bunq.me/t/3a9c4f0e"));
+    }
+}
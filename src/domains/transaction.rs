@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+
+use crate::domains::monetary_account::Amount;
+
+/// Money moves through bunq in several kinds of objects, such as payments, card actions and
+/// requests. This describes what they have in common, so they can be treated uniformly,
+/// e.g. for reporting.
+pub trait Transaction {
+    /// The amount that was moved, negative when it left the account
+    fn amount(&self) -> &Amount;
+
+    /// The moment the transaction was created
+    fn created_at(&self) -> DateTime<Utc>;
+
+    /// The name of the other party of the transaction
+    fn counterparty_name(&self) -> &str;
+
+    fn description(&self) -> &str;
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::api_context::ManagedApiContext;
+use crate::domains::avatar::Avatar;
+use crate::http::{get_authenticated_client, send_request};
+
+/// Confirm the api context still works by fetching the user it acts on behalf of.
+/// This is a cheap call that daemons can make on start up, to fail fast when the stored
+/// context can no longer be used rather than on their first real request.
+pub async fn whoami(api_context: &ManagedApiContext) -> anyhow::Result<WhoAmI> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!("{}/user/{}", base_url, user_id);
+
+    // bunq wraps the user in its kind, e.g. `UserPerson` or `UserCompany`
+    send_request::<HashMap<String, WhoAmI>>(client.get(url))
+        .await
+        .with_context(|| "The api context can no longer be used, set it up again")?
+        .into_iter()
+        .flat_map(|user| user.into_values())
+        .next()
+        .ok_or(anyhow!("User not found in response"))
+}
+
+/// Perform the most lightweight authenticated request there is, e.g. to keep the session
+/// alive in a long running service or to check bunq can be reached.
+/// The session is refreshed along the way when it expired. Returns the round-trip duration.
+pub async fn ping(api_context: &ManagedApiContext) -> anyhow::Result<Duration> {
+    let (user_id, base_url) = {
+        let context = api_context.lock().await;
+        (
+            context.session_context.user_id,
+            context.base_url().to_owned(),
+        )
+    };
+
+    let client = get_authenticated_client(api_context).await?;
+
+    let url = format!("{}/user/{}", base_url, user_id);
+
+    let started_at = Instant::now();
+    send_request::<serde_json::Value>(client.get(url)).await?;
+
+    Ok(started_at.elapsed())
+}
+
+/// The user an api context acts on behalf of
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WhoAmI {
+    pub id: u64,
+    pub display_name: String,
+    /// The picture of the user, see [`crate::domains::avatar::get_avatar`] for its image
+    pub avatar: Option<Avatar>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{process_response_content, BunqResponse};
+
+    #[test]
+    fn user_is_taken_from_any_kind_of_user() {
+        let response = r#"{ "Response": [{ "UserCompany": { "id": 3, "display_name": "bunqyy B.V.", "name": "bunqyy" } }] }"#;
+
+        let user = match process_response_content::<HashMap<String, WhoAmI>>(response).unwrap() {
+            BunqResponse::Success(content) => content
+                .response
+                .into_iter()
+                .flat_map(|user| user.into_values())
+                .next()
+                .unwrap(),
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        assert_eq!(user.id, 3);
+        assert_eq!(user.display_name, "bunqyy B.V.");
+    }
+}
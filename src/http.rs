@@ -1,23 +1,39 @@
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
 
+use anyhow::{anyhow, Context};
 use async_trait::async_trait;
-use reqwest::header::HeaderValue;
-use reqwest::{Client, ClientBuilder, Request, Response};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Client, ClientBuilder, Request, Response, ResponseBuilderExt, StatusCode};
 use reqwest_middleware::{
     ClientBuilder as MiddlewareClientBuilder, ClientWithMiddleware, Middleware, Next,
-    Result as RequestResult,
+    RequestBuilder, Result as RequestResult,
 };
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use serde_json::Value;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::{debug, warn};
+use url::Url;
 
-use crate::api_context::{refresh_session, ManagedApiContext};
-use crate::signing::create_signer;
+use crate::api_context::{
+    refresh_session_if, ApiContext, ManagedApiContext, RateLimitStatus, RequestMetric,
+};
+use crate::common::{BunqyyError, IdempotencyKey};
+use crate::signing::verify_signature;
 
+#[derive(Clone, Copy)]
 pub enum WellKnownBunqHeaders {
     Authentication,
     Signature,
+    #[allow(dead_code)]
+    AttachmentDescription,
+    ClientRequestId,
+    ServerSignature,
 }
 
 /// Bunq has some well known headers that it requires on most of its endpoints
@@ -27,6 +43,27 @@ impl WellKnownBunqHeaders {
         match self {
             WellKnownBunqHeaders::Authentication => "X-Bunq-Client-Authentication",
             WellKnownBunqHeaders::Signature => "X-Bunq-Client-Signature",
+            WellKnownBunqHeaders::AttachmentDescription => "X-Bunq-Attachment-Description",
+            WellKnownBunqHeaders::ClientRequestId => "X-Bunq-Client-Request-Id",
+            WellKnownBunqHeaders::ServerSignature => "X-Bunq-Server-Signature",
+        }
+    }
+}
+
+/// Lets requests carry an optional idempotency key
+#[allow(dead_code)]
+pub trait IdempotentRequestBuilder {
+    fn idempotency_key(self, idempotency_key: Option<&IdempotencyKey>) -> Self;
+}
+
+impl IdempotentRequestBuilder for RequestBuilder {
+    fn idempotency_key(self, idempotency_key: Option<&IdempotencyKey>) -> Self {
+        match idempotency_key {
+            Some(key) => self.header(
+                WellKnownBunqHeaders::ClientRequestId.to_string(),
+                key.as_str(),
+            ),
+            None => self,
         }
     }
 }
@@ -45,29 +82,90 @@ pub async fn get_authenticated_client(
 ) -> anyhow::Result<ClientWithMiddleware> {
     let reqwest_client = get_unauthenticated_client()?;
     let client = MiddlewareClientBuilder::new(reqwest_client)
+        .with(RetryMiddleware {
+            api_context: api_context.clone(),
+        })
+        .with(RequestIdMiddleware)
         .with(SessionRefreshingMiddleware {
             api_context: api_context.clone(),
         })
         .with(SigningMiddleware {
             api_context: api_context.clone(),
         })
+        .with(RateLimitingMiddleware {
+            api_context: api_context.clone(),
+        })
+        .with(TimingMiddleware {
+            api_context: api_context.clone(),
+        })
+        .with(VerifyingMiddleware {
+            api_context: api_context.clone(),
+        })
         .build();
 
     Ok(client)
 }
 
-/// Bunq has a peculiar API response format
-/// The following structs and enum are used to be able to deserialize it
+/// How often a request is attempted when the api context doesn't say otherwise
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(4);
+/// At or below this many remaining requests, the next request waits for the rate limit to reset
+const LOW_RATE_LIMIT_BUDGET: u32 = 1;
+/// Waits for the rate limit that are longer are not waited for, as they're more likely a
+/// misread header than a real limit
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(30);
+
+/// Compute how long to wait before retrying a request for the given (zero based) attempt.
+/// The delay grows exponentially up to `max`, of which a random half is jittered away
+/// so that clients that failed at the same moment don't retry in lockstep.
+pub(crate) fn backoff_delay(attempt: u32, base: Duration, max: Duration) -> Duration {
+    let exponential = base.saturating_mul(2u32.saturating_pow(attempt)).min(max);
+
+    let random = RandomState::new().build_hasher().finish();
+    let jitter = random as f64 / u64::MAX as f64;
+
+    exponential / 2 + (exponential / 2).mul_f64(jitter)
+}
+
+// Bunq has a peculiar API response format
+// The following structs and enum are used to be able to deserialize it
 
 /// A bunq error object. Simply describes the error that occurred and also a translated version
 /// for the user.
-#[derive(Deserialize, Error, Debug)]
+#[derive(Deserialize, Error, Debug, Clone)]
 #[error("Bunq response error: {error_description_translated}")]
 pub struct BunqError {
     pub error_description: String,
     pub error_description_translated: String,
 }
 
+/// Stable identifiers for the error descriptions of bunq that are known, by a part of the
+/// description that identifies them. bunq's wording is fairly stable, but matching on a part
+/// keeps small changes such as punctuation from breaking the mapping.
+const KNOWN_ERRORS: [(&str, &str); 6] = [
+    ("user credentials are incorrect", "USER_CREDENTIALS_INVALID"),
+    ("insufficient authorisation", "INSUFFICIENT_AUTHORISATION"),
+    ("too many requests", "TOO_MANY_REQUESTS"),
+    ("request signature is invalid", "REQUEST_SIGNATURE_INVALID"),
+    ("route not found", "ROUTE_NOT_FOUND"),
+    ("insufficient balance", "INSUFFICIENT_BALANCE"),
+];
+
+impl BunqError {
+    /// A stable identifier for the error to match on, e.g. `USER_CREDENTIALS_INVALID`,
+    /// or `UNKNOWN` for errors that aren't known (yet)
+    pub fn code(&self) -> &'static str {
+        let description = self.error_description.to_lowercase();
+
+        KNOWN_ERRORS
+            .iter()
+            .find(|(known_description, _)| description.contains(known_description))
+            .map(|(_, code)| *code)
+            .unwrap_or("UNKNOWN")
+    }
+}
+
 /// A bunq error response can have multiple error objects
 #[derive(Deserialize)]
 pub struct BunqResponseError {
@@ -75,14 +173,34 @@ pub struct BunqResponseError {
     pub error: Vec<BunqError>,
 }
 
-///
+impl BunqResponseError {
+    /// Turn the response into an error of which the first of bunq's errors is the source,
+    /// so it can be found with `downcast_ref::<BunqError>()`, e.g. to match on its code
+    pub fn into_error(self) -> anyhow::Error {
+        let mut errors = self.error.into_iter();
+
+        let Some(first) = errors.next() else {
+            return anyhow!("bunq responded with an error without a description");
+        };
+
+        errors.fold(anyhow::Error::new(first), |error, other| {
+            error.context(other.to_string())
+        })
+    }
+}
+
+/// A bunq success response wraps its content in a list of objects
 #[derive(Deserialize)]
 pub struct BunqResponseSuccess<Content> {
     #[serde(rename = "Response")]
     pub response: Vec<Content>,
+    /// Only present for listings
+    #[serde(rename = "Pagination", default)]
+    pub pagination: Option<BunqPagination>,
 }
 
-#[derive(Deserialize)]
+/// The cursors of a listing, as paths of the urls to fetch the adjacent pages from
+#[derive(Deserialize, Debug, Clone)]
 #[allow(dead_code)]
 pub struct BunqPagination {
     pub future_url: Option<String>,
@@ -90,20 +208,242 @@ pub struct BunqPagination {
     pub older_url: Option<String>,
 }
 
-#[derive(Deserialize)]
-#[serde(untagged)]
 pub enum BunqResponse<Content> {
     Success(BunqResponseSuccess<Content>),
     Error(BunqResponseError),
 }
 
+/// How much of a raw response is included in errors when the `debug-responses` feature is enabled
+#[cfg(feature = "debug-responses")]
+const RAW_RESPONSE_SNIPPET_LENGTH: usize = 512;
+
 pub fn process_response_content<T>(response_content: &str) -> anyhow::Result<BunqResponse<T>>
 where
     T: DeserializeOwned + Debug,
 {
-    let data = serde_json::from_str::<BunqResponse<T>>(response_content)?;
+    let data = deserialize_response::<T>(response_content).map_err(anyhow::Error::from);
+
+    // The raw response may contain personal data, so it's only included when explicitly asked for
+    #[cfg(feature = "debug-responses")]
+    let data = data.with_context(|| {
+        format!(
+            "Failed to deserialize response: {}",
+            response_snippet(response_content)
+        )
+    });
 
-    Ok(data)
+    data
+}
+
+/// Send a request and deserialize the content of bunq's response. Any error, including
+/// the ones bunq responded with, is given the method and url of the request as context,
+/// so it's clear which of several (concurrent) calls failed.
+pub(crate) async fn send_request<Content>(request: RequestBuilder) -> anyhow::Result<Vec<Content>>
+where
+    Content: DeserializeOwned + Debug,
+{
+    Ok(send_paginated_request(request).await?.0)
+}
+
+/// Send a request for a listing, like [`send_request`], also returning its pagination
+pub(crate) async fn send_paginated_request<Content>(
+    request: RequestBuilder,
+) -> anyhow::Result<(Vec<Content>, Option<BunqPagination>)>
+where
+    Content: DeserializeOwned + Debug,
+{
+    let (client, request) = request.build_split();
+    let request = request?;
+    let description = format!("{} {}", request.method(), request.url());
+
+    let result = async {
+        let response = client.execute(request).await?;
+        let status = response.status();
+        let response_content = response.text().await?;
+
+        check_status(status, &response_content)?;
+
+        match process_response_content::<Content>(response_content.as_str())? {
+            BunqResponse::Success(content) => Ok((content.response, content.pagination)),
+            BunqResponse::Error(errors) => Err(errors.into_error()),
+        }
+    };
+
+    result
+        .await
+        .with_context(|| format!("Request {} failed", description))
+}
+
+/// Send a request of which a part can succeed while another part fails, such as a batch.
+/// bunq then responds with both the content of what it created and the errors of what it
+/// refused, which are both returned rather than failing on the errors. Only when there's no
+/// content at all, the errors fail the request like [`send_request`] does.
+#[cfg(feature = "payments")]
+pub(crate) async fn send_request_allowing_errors<Content>(
+    request: RequestBuilder,
+) -> anyhow::Result<(Vec<Content>, Vec<BunqError>)>
+where
+    Content: DeserializeOwned + Debug,
+{
+    let (client, request) = request.build_split();
+    let request = request?;
+    let description = format!("{} {}", request.method(), request.url());
+
+    let result = async {
+        let response = client.execute(request).await?;
+        let status = response.status();
+        let response_content = response.text().await?;
+
+        check_status(status, &response_content)?;
+
+        deserialize_partial_response(&response_content)
+    };
+
+    result
+        .await
+        .with_context(|| format!("Request {} failed", description))
+}
+
+/// Take both the content and the errors of a response, see [`send_request_allowing_errors`]
+#[cfg(feature = "payments")]
+fn deserialize_partial_response<Content>(
+    response_content: &str,
+) -> anyhow::Result<(Vec<Content>, Vec<BunqError>)>
+where
+    Content: DeserializeOwned,
+{
+    #[derive(Deserialize)]
+    struct PartialResponse<Content> {
+        #[serde(rename = "Response", default = "Vec::new")]
+        response: Vec<Content>,
+        #[serde(rename = "Error", default)]
+        error: Vec<BunqError>,
+    }
+
+    let partial = serde_json::from_str::<PartialResponse<Content>>(response_content)?;
+
+    if partial.response.is_empty() && !partial.error.is_empty() {
+        return Err(BunqResponseError {
+            error: partial.error,
+        }
+        .into_error());
+    }
+
+    Ok((partial.response, partial.error))
+}
+
+/// Deserialize the entries of a listing one by one, leaving out the ones that can't be
+/// deserialized rather than failing the whole listing. The entries that are left out are
+/// logged, so a single malformed entry doesn't hide all the others.
+#[allow(dead_code)]
+pub(crate) fn deserialize_entries<Content>(entries: Vec<Value>) -> Vec<Content>
+where
+    Content: DeserializeOwned,
+{
+    entries
+        .into_iter()
+        .filter_map(|entry| match serde_json::from_value::<Content>(entry) {
+            Ok(content) => Some(content),
+            Err(e) => {
+                warn!("Leaving out an entry that cannot be deserialized: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Turn statuses that don't come with a response in bunq's format into errors before parsing,
+/// so that e.g. the HTML error page of a proxy isn't reported as a deserialization error.
+/// Responses of other statuses, such as bunq's errors for a bad request, are parsed as usual.
+/// When bunq did describe the error of e.g. a 401 or 429, its description is kept as the
+/// source, so it can still be found with `downcast_ref::<BunqError>()` to match on its code.
+fn check_status(status: StatusCode, response_content: &str) -> anyhow::Result<()> {
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let error = match status.as_u16() {
+        401 | 403 => BunqyyError::Unauthorized(status.as_u16()),
+        429 => BunqyyError::RateLimited,
+        500..=599 => BunqyyError::ServerError(status.as_u16()),
+        _ if serde_json::from_str::<Value>(response_content).is_err() => {
+            BunqyyError::UnexpectedStatus(status.as_u16())
+        }
+        _ => return Ok(()),
+    };
+
+    match deserialize_response::<Value>(response_content) {
+        Ok(BunqResponse::Error(errors)) => Err(errors.into_error().context(error)),
+        _ => Err(error.into()),
+    }
+}
+
+/// Resolve a pagination cursor, which bunq gives as a path such as
+/// `/v1/user/1/monetary-account/2/payment?newer_id=3`, against the base url of the api
+#[cfg(feature = "payments")]
+pub(crate) fn pagination_url(base_url: &str, cursor: &str) -> anyhow::Result<String> {
+    Ok(url::Url::parse(base_url)?.join(cursor)?.to_string())
+}
+
+/// Send a request for raw content such as an image or file. Only errors come wrapped
+/// in bunq's response format, which are given the method and url of the request as context.
+pub(crate) async fn send_request_for_bytes(request: RequestBuilder) -> anyhow::Result<Vec<u8>> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let description = format!("{} {}", request.method(), request.url());
+
+    let result = async {
+        let response = client.execute(request).await?;
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(response.bytes().await?.to_vec());
+        }
+
+        let response_content = response.text().await?;
+
+        check_status(status, &response_content)?;
+
+        match process_response_content::<Value>(response_content.as_str())? {
+            BunqResponse::Error(errors) => Err(errors.into_error()),
+            BunqResponse::Success(_) => Err(BunqyyError::UnexpectedStatus(status.as_u16()).into()),
+        }
+    };
+
+    result
+        .await
+        .with_context(|| format!("Request {} failed", description))
+}
+
+/// bunq can respond with an `Error` list even with a successful status code, so its presence
+/// is checked before the content is deserialized. Relying on the order of an untagged enum
+/// would mistake errors for content when the content type accepts (almost) anything.
+fn deserialize_response<T>(response_content: &str) -> serde_json::Result<BunqResponse<T>>
+where
+    T: DeserializeOwned,
+{
+    let value = serde_json::from_str::<Value>(response_content)?;
+
+    if value.get("Error").is_some() {
+        return serde_json::from_value::<BunqResponseError>(value).map(BunqResponse::Error);
+    }
+
+    serde_json::from_value::<BunqResponseSuccess<T>>(value).map(BunqResponse::Success)
+}
+
+/// Take the start of a raw response, cut off at a character boundary
+#[cfg(feature = "debug-responses")]
+fn response_snippet(response_content: &str) -> String {
+    if response_content.len() <= RAW_RESPONSE_SNIPPET_LENGTH {
+        return response_content.to_string();
+    }
+
+    let snippet: String = response_content
+        .chars()
+        .take(RAW_RESPONSE_SNIPPET_LENGTH)
+        .collect();
+
+    format!("{}...", snippet)
 }
 
 struct SigningMiddleware {
@@ -120,74 +460,828 @@ impl Middleware for SigningMiddleware {
         extensions: &mut http::Extensions,
         next: Next<'_>,
     ) -> RequestResult<Response> {
-        let body = req.body();
+        sign_request(&mut req, &mut *self.api_context.lock().await)
+            .map_err(reqwest_middleware::Error::Middleware)?;
 
-        let context = self.api_context.lock().await.clone();
+        debug!("Headers: {:?}", req.headers());
+        debug!("Request debug: {:?}", req);
 
-        if let Some(body) = body {
-            let body_bytes = body.as_bytes().unwrap();
+        next.run(req, extensions).await
+    }
+}
 
-            let key = context.installation_context.private_key_client;
+/// Keeps track of the rate limit headers of every response bunq sends, so it can be seen
+/// how close the client is to being rate limited. When the latest response said the limit is
+/// (nearly) reached, the next request waits until it resets. A request that's rate limited
+/// nonetheless is sent once more after waiting as long as bunq asked.
+struct RateLimitingMiddleware {
+    api_context: ManagedApiContext,
+}
+
+#[async_trait]
+impl Middleware for RateLimitingMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> RequestResult<Response> {
+        let rate_limit = self.api_context.lock().await.rate_limit.clone();
 
-            debug!("Signing request to {}", req.url());
-            let signer = create_signer(key.to_string());
+        if let Some(wait) = rate_limit.and_then(|status| rate_limit_wait(&status, Utc::now())) {
+            debug!("Rate limit is nearly reached, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
 
-            let signed_body = signer(body_bytes);
+        // bunq doesn't handle a request that's rate limited, so it's safe to send it again
+        let replay = req.try_clone();
 
-            req.headers_mut().append(
-                WellKnownBunqHeaders::Signature.to_string(),
-                HeaderValue::from_str(signed_body.as_str()).unwrap(),
+        let response = self.observe(next.clone().run(req, extensions).await?).await;
+
+        let Some(replay) = replay.filter(|_| response.status() == StatusCode::TOO_MANY_REQUESTS)
+        else {
+            return Ok(response);
+        };
+
+        let Some(wait) = rate_limit_headers_of(&response)
+            .retry_after()
+            .filter(|wait| *wait <= MAX_RATE_LIMIT_WAIT)
+        else {
+            return Ok(response);
+        };
+
+        warn!(
+            "Rate limited by bunq, sending the request to {} again in {:?}",
+            response.url(),
+            wait
+        );
+        tokio::time::sleep(wait).await;
+
+        Ok(self.observe(next.run(replay, extensions).await?).await)
+    }
+}
+
+impl RateLimitingMiddleware {
+    async fn observe(&self, response: Response) -> Response {
+        let status = rate_limit_headers_of(&response);
+
+        if !status.headers.is_empty() {
+            debug!(
+                "Rate limit headers of {}: {:?}",
+                response.url(),
+                status.headers
             );
+
+            self.api_context.lock().await.rate_limit = Some(status);
+        }
+
+        response
+    }
+}
+
+fn rate_limit_headers_of(response: &Response) -> RateLimitStatus {
+    RateLimitStatus {
+        headers: rate_limit_headers(response.headers()),
+        observed_at: Utc::now(),
+    }
+}
+
+/// How long to wait before the next request, when the latest response said little to none
+/// are left until the limit resets
+fn rate_limit_wait(status: &RateLimitStatus, now: DateTime<Utc>) -> Option<Duration> {
+    if status.remaining()? > LOW_RATE_LIMIT_BUDGET {
+        return None;
+    }
+
+    let retry_after = status
+        .retry_after()
+        .filter(|wait| *wait <= MAX_RATE_LIMIT_WAIT)?;
+    let reset_at = status.observed_at + chrono::Duration::from_std(retry_after).ok()?;
+
+    (reset_at - now)
+        .to_std()
+        .ok()
+        .filter(|wait| !wait.is_zero())
+}
+
+/// Measures how long every request takes and passes it to the request observer of the api
+/// context, when it has one
+struct TimingMiddleware {
+    api_context: ManagedApiContext,
+}
+
+#[async_trait]
+impl Middleware for TimingMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> RequestResult<Response> {
+        let Some(observer) = self.api_context.lock().await.request_observer.clone() else {
+            return next.run(req, extensions).await;
+        };
+
+        let method = req.method().to_string();
+        let endpoint = endpoint_label(req.url());
+
+        let started_at = Instant::now();
+        let result = next.run(req, extensions).await;
+
+        observer(&RequestMetric {
+            method,
+            endpoint,
+            status: result
+                .as_ref()
+                .ok()
+                .map(|response| response.status().as_u16()),
+            elapsed: started_at.elapsed(),
+        });
+
+        result
+    }
+}
+
+/// The path of the url with the ids replaced, so all requests to an endpoint share a label
+fn endpoint_label(url: &Url) -> String {
+    url.path()
+        .split('/')
+        .map(|segment| {
+            if !segment.is_empty() && segment.bytes().all(|byte| byte.is_ascii_digit()) {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Verifies that responses were signed by bunq, with the public key of the server that was
+/// exchanged during the installation. Only when the api context opted in to it, see
+/// [`ApiContext::with_verified_responses`].
+struct VerifyingMiddleware {
+    api_context: ManagedApiContext,
+}
+
+#[async_trait]
+impl Middleware for VerifyingMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> RequestResult<Response> {
+        let public_key_server = {
+            let context = self.api_context.lock().await;
+            context
+                .verify_responses
+                .then(|| context.installation_context.public_key_server.clone())
+        };
+
+        let Some(public_key_server) = public_key_server else {
+            return next.run(req, extensions).await;
+        };
+
+        let response = next.run(req, extensions).await?;
+
+        // The body can only be read once, so the response is put back together after reading it
+        let status = response.status();
+        let version = response.version();
+        let url = response.url().clone();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+
+        verify_response(&public_key_server, &headers, &body)
+            .map_err(|error| reqwest_middleware::Error::Middleware(error.into()))?;
+
+        let mut builder = http::Response::builder()
+            .status(status)
+            .version(version)
+            .url(url);
+        if let Some(builder_headers) = builder.headers_mut() {
+            *builder_headers = headers;
         }
 
+        Ok(Response::from(builder.body(body).map_err(|error| {
+            reqwest_middleware::Error::Middleware(error.into())
+        })?))
+    }
+}
+
+/// Check the server signature of a response. bunq signs the body of its responses only.
+fn verify_response(
+    public_key_server: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), BunqyyError> {
+    let signature = headers
+        .get(WellKnownBunqHeaders::ServerSignature.to_string())
+        .and_then(|signature| signature.to_str().ok())
+        .ok_or(BunqyyError::ResponseSignatureInvalid)?;
+
+    if verify_signature(public_key_server, body, signature) {
+        Ok(())
+    } else {
+        Err(BunqyyError::ResponseSignatureInvalid)
+    }
+}
+
+/// Pick the headers that are about rate limits, by their lowercase name
+fn rate_limit_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            name.contains("ratelimit") || name.contains("rate-limit") || name == "retry-after"
+        })
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// Add the authentication header to the request and, when it has a body, the signature of
+/// exactly the bytes that will be sent.
+/// Requests without a body (e.g. GET) are not signed. Streaming bodies cannot be signed
+/// up front, so those are rejected rather than sent without a valid signature.
+/// The signer is kept with the context, so its private key is only read once.
+fn sign_request(req: &mut Request, context: &mut ApiContext) -> anyhow::Result<()> {
+    if let Some(body) = req.body() {
+        let body_bytes = body
+            .as_bytes()
+            .ok_or_else(|| anyhow!("Cannot sign a streaming request body for {}", req.url()))?;
+
+        debug!("Signing request to {}", req.url());
+        let signed_body = context.signer()?.sign(body_bytes)?;
+
         req.headers_mut().append(
-            WellKnownBunqHeaders::Authentication.to_string(),
-            HeaderValue::from_str(context.session_context.token.as_str()).unwrap(),
+            WellKnownBunqHeaders::Signature.to_string(),
+            HeaderValue::from_str(signed_body.as_str())
+                .with_context(|| "Signature is not a valid header value")?,
         );
+    }
 
-        debug!("Headers: {:?}", req.headers());
-        debug!("Request debug: {:?}", req);
+    req.headers_mut().append(
+        WellKnownBunqHeaders::Authentication.to_string(),
+        HeaderValue::from_str(context.session_context.token.as_str())
+            .with_context(|| "Session token is not a valid header value")?,
+    );
 
-        next.run(req, extensions).await
-    }
+    Ok(())
 }
 
-struct SessionRefreshingMiddleware {
+/// Sends a request again when bunq failed with a 5xx status or couldn't be reached, see
+/// [`ApiContext::with_max_attempts`]. It's the outermost middleware, so a retried request is
+/// signed again and is still recognised as having been given an idempotency key by the caller.
+struct RetryMiddleware {
     api_context: ManagedApiContext,
 }
 
 #[async_trait]
-impl Middleware for SessionRefreshingMiddleware {
+impl Middleware for RetryMiddleware {
     async fn handle(
         &self,
         req: Request,
         extensions: &mut http::Extensions,
         next: Next<'_>,
     ) -> RequestResult<Response> {
-        if self
+        let max_attempts = self
             .api_context
             .lock()
             .await
-            .session_context
-            .needs_to_be_refreshed()
-        {
-            let refresh_result = refresh_session(self.api_context.clone()).await;
+            .max_attempts
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
 
-            if let Err(e) = refresh_result {
-                return Err(reqwest_middleware::Error::Middleware(e));
+        if !is_retryable(&req) {
+            return next.run(req, extensions).await;
+        }
+
+        let mut attempt = 0;
+
+        loop {
+            // Streaming bodies can't be cloned, those requests are only attempted once
+            let Some(retry) = req.try_clone().filter(|_| attempt + 1 < max_attempts) else {
+                return next.run(req, extensions).await;
+            };
+
+            let result = next.clone().run(retry, extensions).await;
+
+            if !is_transient_failure(&result) {
+                return result;
             }
+
+            let delay = backoff_delay(attempt, RETRY_BASE_DELAY, RETRY_MAX_DELAY);
+            warn!(
+                "Request to {} failed transiently, retrying in {:?}",
+                req.url(),
+                delay
+            );
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Only requests that can't cause something to happen twice are retried: GET requests, and
+/// others when the caller gave them an idempotency key
+fn is_retryable(req: &Request) -> bool {
+    req.method() == reqwest::Method::GET
+        || req
+            .headers()
+            .contains_key(WellKnownBunqHeaders::ClientRequestId.to_string())
+}
+
+/// bunq failed to handle the request or couldn't be reached, which may well pass
+fn is_transient_failure(result: &RequestResult<Response>) -> bool {
+    match result {
+        Ok(response) => response.status().is_server_error(),
+        Err(reqwest_middleware::Error::Reqwest(error)) => {
+            error.is_connect() || error.is_timeout() || error.is_request()
         }
+        Err(reqwest_middleware::Error::Middleware(_)) => false,
+    }
+}
+
+/// Gives every request that wasn't given an idempotency key a random one. It comes right
+/// after retrying, so a request that's sent again after refreshing the session keeps its key.
+struct RequestIdMiddleware;
+
+#[async_trait]
+impl Middleware for RequestIdMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> RequestResult<Response> {
+        add_request_id(&mut req);
+
+        next.run(req, extensions).await
+    }
+}
+
+fn add_request_id(req: &mut Request) {
+    let header = WellKnownBunqHeaders::ClientRequestId.to_string();
+
+    if !req.headers().contains_key(header) {
+        let key = IdempotencyKey::new();
+        if let Ok(value) = HeaderValue::from_str(key.as_str()) {
+            req.headers_mut().insert(header, value);
+        }
+    }
+}
+
+struct SessionRefreshingMiddleware {
+    api_context: ManagedApiContext,
+}
+
+#[async_trait]
+impl Middleware for SessionRefreshingMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> RequestResult<Response> {
+        let session_token = {
+            let api_context = self.api_context.lock().await;
+
+            (!api_context.session_context.needs_to_be_refreshed())
+                .then(|| api_context.session_context.token.clone())
+        };
+
+        let session_token = match session_token {
+            Some(session_token) => session_token,
+            None => {
+                refresh_session_if(&self.api_context, |api_context| {
+                    api_context.session_context.needs_to_be_refreshed()
+                })
+                .await
+                .map_err(reqwest_middleware::Error::Middleware)?;
+
+                self.api_context.lock().await.session_context.token.clone()
+            }
+        };
+
+        // bunq can invalidate a session before it expires, e.g. when the password changes.
+        // Keep a copy of the request so it can be replayed once with a fresh session.
+        let replay = req.try_clone();
+
+        let response = next.clone().run(req, extensions).await?;
+
+        let Some(replay) = replay.filter(|_| is_session_expired(&response)) else {
+            return Ok(response);
+        };
 
-        let res = next.run(req, extensions).await;
-        res
+        debug!("Session was rejected by bunq, refreshing it and replaying the request");
+
+        // Requests that were rejected together refresh the session once
+        refresh_session_if(&self.api_context, |api_context| {
+            api_context.session_context.token == session_token
+        })
+        .await
+        .map_err(reqwest_middleware::Error::Middleware)?;
+
+        next.run(replay, extensions).await
     }
 }
 
+/// bunq answers requests made with a session it no longer accepts with a 401
+fn is_session_expired(response: &Response) -> bool {
+    response.status() == StatusCode::UNAUTHORIZED
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::http::{process_response_content, BunqResponse};
+    use crate::api_context::{
+        ApiContext, Environment, InstallationContext, RateLimitStatus, SessionContext,
+        SessionUserApiKey, UserInformation,
+    };
+    use crate::common::BunqyyError;
+    use crate::http::{
+        add_request_id, backoff_delay, check_status, endpoint_label, is_retryable,
+        is_session_expired, is_transient_failure, process_response_content, rate_limit_headers,
+        rate_limit_wait, send_request, sign_request, verify_response, BunqError, BunqResponse,
+        WellKnownBunqHeaders,
+    };
+    use crate::signing::{create_signer, generate_keypair};
+    use chrono::Utc;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::{Method, Request, StatusCode};
     use serde::Deserialize;
     use serde_json::Value;
+    use std::time::Duration;
+    use url::Url;
+
+    fn api_context_with_private_key(private_key_client: String) -> ApiContext {
+        let user = UserInformation {
+            id: 1,
+            display_name: "bunqyy".to_string(),
+            public_nick_name: "bunqyy".to_string(),
+            session_timeout: 600,
+            alias: vec![],
+        };
+
+        ApiContext {
+            api_key: "api-key".to_string(),
+            environment: Environment::SANDBOX,
+            base_url_override: None,
+            rate_limit: None,
+            request_observer: None,
+            verify_responses: false,
+            max_attempts: None,
+            signer: None,
+            refresh_lock: std::sync::Arc::default(),
+            device_id: None,
+            installation_context: InstallationContext {
+                token: "installation-token".to_string(),
+                private_key_client,
+                public_key_client: String::new(),
+                public_key_server: String::new(),
+            },
+            session_context: SessionContext {
+                token: "session-token".to_string(),
+                valid_until: Utc::now(),
+                user_id: 1,
+                user_api_key: SessionUserApiKey {
+                    id: 1,
+                    requested_by_user: user.clone(),
+                    granted_by_user: user,
+                },
+            },
+        }
+    }
+
+    #[cfg(feature = "payments")]
+    #[test]
+    fn pagination_is_deserialized_and_resolved() {
+        let response = r#"
+        {
+            "Response": [],
+            "Pagination": {
+                "future_url": null,
+                "newer_url": "/v1/user/1/monetary-account/2/payment?count=200&newer_id=3",
+                "older_url": null
+            }
+        }
+        "#;
+
+        use crate::http::pagination_url;
+
+        let pagination = match process_response_content::<Value>(response).unwrap() {
+            BunqResponse::Success(content) => content.pagination.unwrap(),
+            BunqResponse::Error(_) => panic!("Expected success"),
+        };
+
+        assert_eq!(
+            pagination_url(
+                Environment::SANDBOX.base_url(),
+                &pagination.newer_url.unwrap()
+            )
+            .unwrap(),
+            "https://public-api.sandbox.bunq.com/v1/user/1/monetary-account/2/payment?count=200&newer_id=3"
+        );
+    }
+
+    #[cfg(feature = "payments")]
+    #[test]
+    fn partial_responses_keep_both_content_and_errors() {
+        use crate::http::deserialize_partial_response;
+
+        let response = r#"
+        {
+            "Response": [{ "Id": { "id": 42 } }],
+            "Error": [
+                {
+                    "error_description": "Insufficient balance",
+                    "error_description_translated": "Insufficient balance"
+                }
+            ]
+        }
+        "#;
+
+        let (created, errors) = deserialize_partial_response::<Value>(response).unwrap();
+        assert_eq!(created[0]["Id"]["id"], 42);
+        assert_eq!(errors[0].code(), "INSUFFICIENT_BALANCE");
+
+        let refused = r#"
+        {
+            "Error": [
+                {
+                    "error_description": "Insufficient balance",
+                    "error_description_translated": "Insufficient balance"
+                }
+            ]
+        }
+        "#;
+
+        let error = deserialize_partial_response::<Value>(refused).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BunqError>().unwrap().code(),
+            "INSUFFICIENT_BALANCE"
+        );
+    }
+
+    #[test]
+    fn known_errors_have_a_code() {
+        let error = |description: &str| BunqError {
+            error_description: description.to_string(),
+            error_description_translated: description.to_string(),
+        };
+
+        assert_eq!(
+            error("User credentials are incorrect. Incorrect API key or IP address.").code(),
+            "USER_CREDENTIALS_INVALID"
+        );
+        assert_eq!(
+            error(
+                "Too many requests. You can do a maximum of 3 calls per 3 second to this endpoint."
+            )
+            .code(),
+            "TOO_MANY_REQUESTS"
+        );
+        assert_eq!(error("Something went wrong").code(), "UNKNOWN");
+    }
+
+    #[test]
+    fn statuses_without_a_bunq_response_are_errors() {
+        let html = "<html><body>502 Bad Gateway</body></html>";
+        let bunq_error =
+            r#"{"Error": [{"error_description": "Bad", "error_description_translated": "Bad"}]}"#;
+
+        let error_of = |status: StatusCode, response_content: &str| {
+            check_status(status, response_content).unwrap_err()
+        };
+
+        assert!(matches!(
+            error_of(StatusCode::BAD_GATEWAY, html).downcast_ref::<BunqyyError>(),
+            Some(BunqyyError::ServerError(502))
+        ));
+        assert!(matches!(
+            error_of(StatusCode::FORBIDDEN, html).downcast_ref::<BunqyyError>(),
+            Some(BunqyyError::Unauthorized(403))
+        ));
+        assert!(error_of(StatusCode::FORBIDDEN, html)
+            .downcast_ref::<BunqError>()
+            .is_none());
+        assert!(matches!(
+            error_of(StatusCode::NOT_FOUND, html).downcast_ref::<BunqyyError>(),
+            Some(BunqyyError::UnexpectedStatus(404))
+        ));
+        assert!(check_status(StatusCode::BAD_REQUEST, bunq_error).is_ok());
+
+        // bunq's own description of the error is kept, so its code can be matched on
+        let too_many_requests = r#"{"Error": [{"error_description": "Too many requests. You can do a maximum of 3 calls per 3 second to this endpoint.", "error_description_translated": "Too many requests."}]}"#;
+        let rate_limited = error_of(StatusCode::TOO_MANY_REQUESTS, too_many_requests);
+        assert!(matches!(
+            rate_limited.downcast_ref::<BunqyyError>(),
+            Some(BunqyyError::RateLimited)
+        ));
+        assert_eq!(
+            rate_limited.downcast_ref::<BunqError>().unwrap().code(),
+            "TOO_MANY_REQUESTS"
+        );
+        assert!(check_status(StatusCode::OK, "").is_ok());
+    }
+
+    #[test]
+    fn responses_must_be_signed_by_the_server() {
+        let server_keypair = generate_keypair();
+        let body = r#"{"Response": []}"#.as_bytes();
+        let signature = create_signer(server_keypair.private_key_pem)(body).unwrap();
+
+        let mut headers = HeaderMap::new();
+        assert!(matches!(
+            verify_response(&server_keypair.public_key_pem, &headers, body),
+            Err(BunqyyError::ResponseSignatureInvalid)
+        ));
+
+        headers.insert(
+            WellKnownBunqHeaders::ServerSignature.to_string(),
+            HeaderValue::from_str(&signature).unwrap(),
+        );
+        assert!(verify_response(&server_keypair.public_key_pem, &headers, body).is_ok());
+        assert!(matches!(
+            verify_response(&server_keypair.public_key_pem, &headers, b"{}"),
+            Err(BunqyyError::ResponseSignatureInvalid)
+        ));
+    }
+
+    #[test]
+    fn requests_get_a_request_id_unless_they_have_one() {
+        let header = WellKnownBunqHeaders::ClientRequestId.to_string();
+        let url = Url::parse("https://bunq.test/v1/user/1/payment").unwrap();
+
+        let mut request = Request::new(Method::POST, url.clone());
+        add_request_id(&mut request);
+        assert_eq!(request.headers()[header].len(), 32);
+
+        let mut request = Request::new(Method::POST, url);
+        request
+            .headers_mut()
+            .insert(header, HeaderValue::from_static("my-key"));
+        add_request_id(&mut request);
+        assert_eq!(request.headers()[header], "my-key");
+    }
+
+    #[test]
+    fn ids_are_left_out_of_endpoint_labels() {
+        let url =
+            Url::parse("https://api.bunq.com/v1/user/12/monetary-account/34/payment?count=200")
+                .unwrap();
+
+        assert_eq!(
+            endpoint_label(&url),
+            "/v1/user/{id}/monetary-account/{id}/payment"
+        );
+    }
+
+    #[test]
+    fn only_rate_limit_headers_are_kept() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("X-RateLimit-Remaining", "2".parse().unwrap());
+        headers.insert("Retry-After", "3".parse().unwrap());
+        headers.insert("X-Bunq-Client-Response-Id", "abc".parse().unwrap());
+
+        let rate_limit = rate_limit_headers(&headers);
+
+        assert_eq!(rate_limit.len(), 2);
+        assert_eq!(rate_limit["x-ratelimit-remaining"], "2");
+        assert_eq!(rate_limit["retry-after"], "3");
+    }
+
+    #[test]
+    fn next_request_waits_when_the_rate_limit_is_nearly_reached() {
+        let observed_at = Utc::now();
+        let status = |remaining: &str, retry_after: &str| RateLimitStatus {
+            headers: [
+                ("x-ratelimit-remaining".to_string(), remaining.to_string()),
+                ("retry-after".to_string(), retry_after.to_string()),
+            ]
+            .into(),
+            observed_at,
+        };
+
+        assert_eq!(status("1", "3").remaining(), Some(1));
+        assert_eq!(status("1", "3").retry_after(), Some(Duration::from_secs(3)));
+
+        let wait = rate_limit_wait(
+            &status("1", "3"),
+            observed_at + chrono::Duration::seconds(1),
+        );
+        assert_eq!(wait, Some(Duration::from_secs(2)));
+
+        // Plenty left, the limit already reset, or a wait that can't be right
+        assert_eq!(rate_limit_wait(&status("2", "3"), observed_at), None);
+        assert_eq!(
+            rate_limit_wait(
+                &status("0", "3"),
+                observed_at + chrono::Duration::seconds(5)
+            ),
+            None
+        );
+        assert_eq!(
+            rate_limit_wait(&status("0", "1700000000"), observed_at),
+            None
+        );
+    }
+
+    #[test]
+    fn backoff_delay_grows_and_is_capped() {
+        let base = Duration::from_millis(100);
+        let max = Duration::from_secs(1);
+
+        for attempt in 0..10 {
+            let expected = (base * 2u32.pow(attempt)).min(max);
+            let delay = backoff_delay(attempt, base, max);
+
+            assert!(delay >= expected / 2);
+            assert!(delay <= expected);
+        }
+    }
+
+    #[test]
+    fn request_without_body_is_authenticated_but_not_signed() {
+        let mut context = api_context_with_private_key(String::new());
+        let mut request = Request::new(
+            Method::GET,
+            Url::parse("https://public-api.sandbox.bunq.com/v1/user").unwrap(),
+        );
+
+        sign_request(&mut request, &mut context).unwrap();
+
+        assert_eq!(
+            request
+                .headers()
+                .get(WellKnownBunqHeaders::Authentication.to_string())
+                .unwrap(),
+            "session-token"
+        );
+        assert!(request
+            .headers()
+            .get(WellKnownBunqHeaders::Signature.to_string())
+            .is_none());
+    }
+
+    #[test]
+    fn request_with_body_is_signed() {
+        let keypair = generate_keypair();
+        let mut context = api_context_with_private_key(keypair.private_key_pem.clone());
+
+        let request = || {
+            let mut request = Request::new(
+                Method::POST,
+                Url::parse("https://public-api.sandbox.bunq.com/v1/user").unwrap(),
+            );
+            *request.body_mut() = Some("{}".into());
+            request
+        };
+        let signature = |request: &Request| {
+            request
+                .headers()
+                .get(WellKnownBunqHeaders::Signature.to_string())
+                .unwrap()
+                .clone()
+        };
+
+        let mut first = request();
+        sign_request(&mut first, &mut context).unwrap();
+        assert_eq!(signature(&first).len(), 344);
+        assert!(context
+            .signer
+            .as_ref()
+            .unwrap()
+            .is_for(&keypair.private_key_pem));
+
+        // The signer is kept, and replaced along with the private key
+        let mut second = request();
+        sign_request(&mut second, &mut context).unwrap();
+        assert_eq!(signature(&first), signature(&second));
+
+        let rotated = generate_keypair();
+        context.installation_context.private_key_client = rotated.private_key_pem.clone();
+        let mut third = request();
+        sign_request(&mut third, &mut context).unwrap();
+        assert_ne!(signature(&first), signature(&third));
+        assert!(context
+            .signer
+            .as_ref()
+            .unwrap()
+            .is_for(&rotated.private_key_pem));
+    }
+
+    #[test]
+    fn request_with_a_corrupt_private_key_fails_to_sign() {
+        let mut context = api_context_with_private_key("corrupt".to_string());
+
+        let mut request = Request::new(
+            Method::POST,
+            Url::parse("https://public-api.sandbox.bunq.com/v1/user").unwrap(),
+        );
+        *request.body_mut() = Some("{}".into());
+
+        assert!(sign_request(&mut request, &mut context).is_err());
+    }
 
     #[test]
     fn success_response_should_result_in_id() {
@@ -285,6 +1379,25 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(feature = "debug-responses")]
+    fn failed_deserialization_includes_raw_response() {
+        let response = r#"{"Response": [{"Id": {"id": "not-a-number"}}]}"#;
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Content {
+            #[serde(rename = "Id")]
+            id: u64,
+        }
+
+        let Err(error) = process_response_content::<Content>(response) else {
+            panic!("Expected a deserialization error");
+        };
+
+        assert!(format!("{:#}", error).contains("not-a-number"));
+    }
+
     #[test]
     fn error_response_should_result_in_error() {
         let response = r#"
@@ -308,4 +1421,108 @@ mod tests {
             _ => panic!("Expected error"),
         }
     }
+
+    #[test]
+    fn error_within_success_response_should_result_in_error() {
+        let response = r#"
+        {
+            "Response": [
+                {
+                    "Id": {
+                        "id": 1
+                    }
+                }
+            ],
+            "Error": [
+                {
+                    "error_description": "Payment 2 of the batch failed",
+                    "error_description_translated": "Payment 2 of the batch failed"
+                }
+            ]
+        }
+        "#;
+
+        let result = process_response_content::<Value>(response).unwrap();
+
+        match result {
+            BunqResponse::Error(content) => {
+                assert_eq!(
+                    content.error[0].error_description,
+                    "Payment 2 of the batch failed"
+                );
+            }
+            _ => panic!("Expected error"),
+        }
+    }
+
+    #[test]
+    fn only_unauthorized_responses_are_treated_as_an_expired_session() {
+        let response = |status: u16| {
+            reqwest::Response::from(
+                http::Response::builder()
+                    .status(status)
+                    .body(String::new())
+                    .unwrap(),
+            )
+        };
+
+        assert!(is_session_expired(&response(401)));
+        assert!(!is_session_expired(&response(200)));
+        assert!(!is_session_expired(&response(400)));
+    }
+
+    #[test]
+    fn only_requests_that_are_safe_to_repeat_are_retried() {
+        let url = Url::parse("https://api.bunq.com/v1/user/1").unwrap();
+
+        assert!(is_retryable(&Request::new(Method::GET, url.clone())));
+        assert!(!is_retryable(&Request::new(Method::POST, url.clone())));
+
+        let mut with_key = Request::new(Method::POST, url);
+        with_key.headers_mut().insert(
+            WellKnownBunqHeaders::ClientRequestId.to_string(),
+            HeaderValue::from_static("key"),
+        );
+        assert!(is_retryable(&with_key));
+    }
+
+    #[tokio::test]
+    async fn server_errors_and_unreachable_servers_are_transient() {
+        let response = |status: u16| {
+            Ok(reqwest::Response::from(
+                http::Response::builder()
+                    .status(status)
+                    .body(String::new())
+                    .unwrap(),
+            ))
+        };
+
+        assert!(is_transient_failure(&response(503)));
+        assert!(is_transient_failure(&response(500)));
+        assert!(!is_transient_failure(&response(200)));
+        assert!(!is_transient_failure(&response(400)));
+
+        // Nothing listens on the discard port, so the connection is refused
+        let refused = reqwest::get("http://127.0.0.1:9/").await.unwrap_err();
+        assert!(is_transient_failure(&Err(refused.into())));
+        assert!(!is_transient_failure(&Err(
+            reqwest_middleware::Error::Middleware(anyhow::anyhow!("Cannot sign"))
+        )));
+    }
+
+    #[tokio::test]
+    async fn failed_requests_mention_method_and_url() {
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+
+        // Nothing listens on the discard port, so the connection is refused
+        let Err(error) = send_request::<Value>(client.get("http://127.0.0.1:9/v1/user/1")).await
+        else {
+            panic!("Expected the request to fail");
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "Request GET http://127.0.0.1:9/v1/user/1 failed"
+        );
+    }
 }
@@ -1,5 +1,10 @@
 pub mod api_context;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod bunq_timestamp;
+pub mod client;
 pub mod common;
 pub mod domains;
 mod http;
-mod signing;
+pub mod serde_helpers;
+pub mod signing;
@@ -0,0 +1,86 @@
+//! Deserialize helpers for the places where bunq's JSON is inconsistent, such as booleans
+//! and numbers that are sometimes sent as strings. Use them with `#[serde(deserialize_with)]`
+//! when modelling a field of which the type varies.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOr<T> {
+    String(String),
+    Value(T),
+}
+
+/// Deserialize a boolean that's either a JSON boolean or the string `"true"` or `"false"`
+pub fn deserialize_string_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match StringOr::<bool>::deserialize(deserializer)? {
+        StringOr::Value(value) => Ok(value),
+        StringOr::String(value) => match value.trim().to_lowercase().as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(D::Error::custom(format!(
+                "expected a boolean, got \"{}\"",
+                value
+            ))),
+        },
+    }
+}
+
+/// Deserialize a number that's either a JSON number or a string containing one, e.g. `"3"`
+pub fn deserialize_string_number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + Deserialize<'de>,
+    T::Err: Display,
+{
+    match StringOr::<T>::deserialize(deserializer)? {
+        StringOr::Value(value) => Ok(value),
+        StringOr::String(value) => value
+            .trim()
+            .parse()
+            .map_err(|error| D::Error::custom(format!("invalid number \"{}\": {}", value, error))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    struct Flags {
+        #[serde(deserialize_with = "deserialize_string_bool")]
+        enabled: bool,
+        #[serde(deserialize_with = "deserialize_string_number")]
+        limit: u32,
+    }
+
+    #[test]
+    fn bools_are_deserialized_from_strings_and_booleans() {
+        let from_string: Flags =
+            serde_json::from_str(r#"{ "enabled": "true", "limit": 1 }"#).unwrap();
+        let from_bool: Flags = serde_json::from_str(r#"{ "enabled": false, "limit": 1 }"#).unwrap();
+
+        assert!(from_string.enabled);
+        assert!(!from_bool.enabled);
+        assert!(serde_json::from_str::<Flags>(r#"{ "enabled": "yes", "limit": 1 }"#).is_err());
+    }
+
+    #[test]
+    fn numbers_are_deserialized_from_strings_and_numbers() {
+        let from_string: Flags =
+            serde_json::from_str(r#"{ "enabled": true, "limit": "25" }"#).unwrap();
+        let from_number: Flags =
+            serde_json::from_str(r#"{ "enabled": true, "limit": 25 }"#).unwrap();
+
+        assert_eq!(from_string.limit, 25);
+        assert_eq!(from_number.limit, 25);
+        assert!(serde_json::from_str::<Flags>(r#"{ "enabled": true, "limit": "lots" }"#).is_err());
+    }
+}